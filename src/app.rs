@@ -4,7 +4,7 @@ use std::{
     any::Any,
     fmt::Debug,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{Arc, Mutex, RwLock},
 };
 
 use crate::{
@@ -12,8 +12,9 @@ use crate::{
     endpoint::BoxedEndpoint,
     endpoint::Endpoint,
     extract::Extract,
+    host::{request_host, select_router, HostPattern},
     middleware::{logger::RootLogger, RequestContext},
-    router::{EndpointData, Resource, RouteResult, Router},
+    router::{EndpointData, Fallbacks, Resource, RouteInfo, RouteResult, Router},
     Middleware, Request, Response, RouteMatch,
 };
 
@@ -74,7 +75,8 @@ use crate::{
 pub struct App<Data> {
     data: Data,
     router: Router<Data>,
-    default_handler: EndpointData<Data>,
+    hosts: Vec<(HostPattern, Router<Data>)>,
+    fallbacks: Fallbacks<Data>,
 }
 
 impl<Data: Clone + Send + Sync + 'static> App<Data> {
@@ -84,10 +86,8 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
         let mut app = App {
             data,
             router: Router::new(),
-            default_handler: EndpointData {
-                endpoint: BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
-                store: Store::new(),
-            },
+            hosts: Vec::new(),
+            fallbacks: Fallbacks::new(),
         };
 
         // Add RootLogger as a default middleware
@@ -108,23 +108,78 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
         &mut self.router
     }
 
+    /// Get the router for requests to `host`.
+    ///
+    /// `host` is either an exact host name such as `api.example.com`, or a wildcard such as
+    /// `*.example.com` matching a single subdomain label, which endpoints can extract with
+    /// [`head::Subdomain`](head/struct.Subdomain.html). Requests to hosts without a router of
+    /// their own, or without a `Host` header, are routed by the top-level router. Exact host names
+    /// take precedence over wildcards.
+    ///
+    /// Each host router has its own resources, middleware and configuration. It starts out with the
+    /// middleware applied to the app so far, like a subrouter set up with `Resource::nest`, and
+    /// uses the configuration of the app as defaults.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// let mut app = tide::App::new(());
+    /// app.at("/").get(async || "www");
+    /// app.host("api.example.com").at("/").get(async || "api");
+    /// app.host("*.example.com").at("/").get(async || "tenant");
+    /// ```
+    pub fn host(&mut self, host: &str) -> &mut Router<Data> {
+        let pattern = HostPattern::parse(host);
+        let idx = match self.hosts.iter().position(|(p, _)| *p == pattern) {
+            Some(idx) => idx,
+            None => {
+                let router = self.router.new_sibling();
+                self.hosts.push((pattern, router));
+                self.hosts.len() - 1
+            }
+        };
+        &mut self.hosts[idx].1
+    }
+
     /// Add a new resource at `path`.
     /// See [Router.at](struct.Router.html#method.at) for details.
     pub fn at<'a>(&'a mut self, path: &'a str) -> Resource<'a, Data> {
         self.router.at(path)
     }
 
+    /// List the resources registered with the app, ordered by path.
+    /// See [Router.routes](struct.Router.html#method.routes) for details.
+    pub fn routes(&self) -> impl Iterator<Item = RouteInfo<'_, Data>> {
+        self.router.routes()
+    }
+
     /// Set the default handler for the app, a fallback function when there is no match to the route requested
+    ///
+    /// Subrouters can override it for the paths beneath them with
+    /// [Router.default_handler](struct.Router.html#method.default_handler).
     pub fn default_handler<T: Endpoint<Data, U>, U>(
         &mut self,
         handler: T,
     ) -> &mut EndpointData<Data> {
-        let endpoint = EndpointData {
-            endpoint: BoxedEndpoint::new(handler),
-            store: self.router.store_base.clone(),
-        };
-        self.default_handler = endpoint;
-        &mut self.default_handler
+        let mut endpoint = EndpointData::new(BoxedEndpoint::new(handler), &[]);
+        endpoint.store = self.router.store_base.clone();
+        self.fallbacks.not_found = endpoint;
+        &mut self.fallbacks.not_found
+    }
+
+    /// Set the handler for requests whose path matches a resource, but whose method does not
+    /// match any of its endpoints.
+    ///
+    /// By default the app responds with `405 Method Not Allowed`. The handler can use the
+    /// [`AllowedMethods`](struct.AllowedMethods.html) extractor to learn which methods the
+    /// resource accepts. If the response of the handler has no `Allow` header, one is added.
+    pub fn method_not_allowed_handler<T: Endpoint<Data, U>, U>(
+        &mut self,
+        handler: T,
+    ) -> &mut EndpointData<Data> {
+        let mut endpoint = EndpointData::new(BoxedEndpoint::new(handler), &[]);
+        endpoint.store = self.router.store_base.clone();
+        self.fallbacks.method_not_allowed = endpoint;
+        &mut self.fallbacks.method_not_allowed
     }
 
     /// Apply `middleware` to the whole app. Note that the order of nesting subrouters and applying
@@ -145,12 +200,18 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
     }
 
     /// Make this app into an `HttpService`.
-    pub fn into_http_service(mut self) -> Server<Data> {
-        self.router.apply_default_config();
+    pub fn into_http_service(self) -> Server<Data> {
+        let mut routing = Routing {
+            router: Arc::new(self.router),
+            hosts: Arc::new(self.hosts),
+        };
+        Arc::make_mut(&mut routing.router).derive_config(&Store::new());
+        routing.derive_host_config();
         Server {
             data: self.data,
-            router: Arc::new(self.router),
-            default_handler: Arc::new(self.default_handler),
+            routing: Arc::new(RwLock::new(Arc::new(routing))),
+            updates: Arc::new(Mutex::new(())),
+            fallbacks: Arc::new(self.fallbacks),
         }
     }
 
@@ -173,8 +234,142 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
 #[derive(Clone)]
 pub struct Server<Data> {
     data: Data,
+    routing: Arc<RwLock<Arc<Routing<Data>>>>,
+    /// Serializes updates through `RouterHandle`, so that none of them is lost.
+    updates: Arc<Mutex<()>>,
+    fallbacks: Arc<Fallbacks<Data>>,
+}
+
+/// The routers of a running server, replaced as a whole when the routes change.
+///
+/// Cloning the routing only clones the pointers to the routers, so that an update copies just the
+/// routers it changes.
+#[derive(Clone)]
+struct Routing<Data> {
     router: Arc<Router<Data>>,
-    default_handler: Arc<EndpointData<Data>>,
+    hosts: Arc<Vec<(HostPattern, Router<Data>)>>,
+}
+
+impl<Data: Clone + Send + Sync + 'static> Routing<Data> {
+    /// Derive the configuration of the host routers, which default to the configuration of the
+    /// top-level router.
+    fn derive_host_config(&mut self) {
+        let defaults = &self.router.store_base;
+        for (_, router) in Arc::make_mut(&mut self.hosts) {
+            router.derive_config(defaults);
+        }
+    }
+}
+
+impl<Data: Clone + Send + Sync + 'static> Server<Data> {
+    /// Get a handle for changing the routes of this server while it is running.
+    pub fn handle(&self) -> RouterHandle<Data> {
+        RouterHandle {
+            routing: self.routing.clone(),
+            updates: self.updates.clone(),
+        }
+    }
+}
+
+/// A handle for changing the routes of a running [`Server`](struct.Server.html).
+///
+/// Changes are applied to a copy of the routing table, which then atomically replaces the table
+/// used for new requests. Requests already in flight finish on the table they were routed with.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await)]
+/// let mut app = tide::App::new(());
+/// app.at("/").get(async || "index");
+/// let server = app.into_http_service();
+///
+/// let handle = server.handle();
+/// handle.update(|router| {
+///     router.at("/extension").get(async || "loaded at runtime");
+/// });
+/// handle.update_host("acme.example.com", |router| {
+///     router.at("/").get(async || "acme");
+/// });
+/// ```
+#[derive(Clone)]
+pub struct RouterHandle<Data> {
+    routing: Arc<RwLock<Arc<Routing<Data>>>>,
+    updates: Arc<Mutex<()>>,
+}
+
+impl<Data: Clone + Send + Sync + 'static> RouterHandle<Data> {
+    /// Change the top-level router with `patch`.
+    ///
+    /// The router keeps its resources, middleware and configuration, so that `patch` can add or
+    /// override single routes. Resources added by `patch` use the middleware and configuration of
+    /// the app. Configuration set with `Router::config` in `patch` changes the defaults of all
+    /// routes of the app, including those of host routers.
+    pub fn update(&self, patch: impl FnOnce(&mut Router<Data>)) {
+        self.modify(|routing| {
+            let router = Arc::make_mut(&mut routing.router);
+            let config_version = router.config_version;
+            patch(router);
+            router.derive_config(&Store::new());
+            if router.config_version != config_version {
+                routing.derive_host_config();
+            }
+        })
+    }
+
+    /// Change the router for requests to `host` with `patch`, setting up a new host router like
+    /// `App::host` if there is none yet.
+    pub fn update_host(&self, host: &str, patch: impl FnOnce(&mut Router<Data>)) {
+        let pattern = HostPattern::parse(host);
+        self.modify(|routing| {
+            let hosts = Arc::make_mut(&mut routing.hosts);
+            let idx = match hosts.iter().position(|(p, _)| *p == pattern) {
+                Some(idx) => idx,
+                None => {
+                    let router = routing.router.new_sibling();
+                    hosts.push((pattern, router));
+                    hosts.len() - 1
+                }
+            };
+            let router = &mut hosts[idx].1;
+            patch(router);
+            router.derive_config(&routing.router.store_base);
+        })
+    }
+
+    /// Replace the top-level router by `router`.
+    ///
+    /// The configuration of the app serves as the default configuration of `router`. Its
+    /// middleware is used as is; start out from [`RouterHandle::new_router`] to keep the
+    /// middleware of the app.
+    ///
+    /// [`RouterHandle::new_router`]: #method.new_router
+    pub fn replace(&self, mut router: Router<Data>) {
+        self.modify(move |routing| {
+            router.store_base.merge(&routing.router.store_base);
+            router.derive_config(&Store::new());
+            routing.router = Arc::new(router);
+            routing.derive_host_config();
+        })
+    }
+
+    /// Create an empty router using the middleware applied to the app, to be passed to `replace`.
+    pub fn new_router(&self) -> Router<Data> {
+        self.current().router.new_sibling()
+    }
+
+    fn current(&self) -> Arc<Routing<Data>> {
+        self.routing.read().unwrap().clone()
+    }
+
+    fn modify(&self, modify: impl FnOnce(&mut Routing<Data>)) {
+        // A panic inside `modify` leaves the routing table untouched, so the lock is still valid.
+        let _updating = self.updates.lock().unwrap_or_else(|err| err.into_inner());
+        // Routers are only copied once `modify` changes them.
+        let mut routing = (*self.current()).clone();
+        modify(&mut routing);
+        *self.routing.write().unwrap() = Arc::new(routing);
+    }
 }
 
 impl<Data> HttpService for Server<Data>
@@ -189,29 +384,57 @@ where
         future::ok(())
     }
 
-    fn respond(&self, _conn: &mut (), req: http_service::Request) -> Self::Fut {
+    fn respond(&self, _conn: &mut (), mut req: http_service::Request) -> Self::Fut {
         let data = self.data.clone();
-        let router = self.router.clone();
-        let default_handler = self.default_handler.clone();
+        let routing = self.routing.read().unwrap().clone();
+        let fallbacks = self.fallbacks.clone();
         let path = req.uri().path().to_owned();
-        let method = req.method().to_owned();
+        let host = request_host(&req);
 
         FutureObj::new(Box::new(
             async move {
+                let host = host.as_ref().map(String::as_str);
+                let (router, subdomain) = select_router(&routing.router, &routing.hosts, host);
+                req.extensions_mut().insert(router.urls().clone());
+                if let Some(subdomain) = subdomain {
+                    req.extensions_mut().insert(subdomain);
+                }
+
                 let RouteResult {
                     endpoint,
                     params,
                     middleware,
-                } = router.route(&path, &method, &default_handler);
+                    allowed,
+                    redirect,
+                    rejected,
+                } = router.route(&path, &req, &fallbacks);
+
+                if let Some(allowed) = &allowed {
+                    req.extensions_mut().insert(allowed.clone());
+                }
+                if let Some(redirect) = redirect {
+                    req.extensions_mut().insert(redirect);
+                }
+                if let Some(rejected) = rejected {
+                    req.extensions_mut().insert(rejected);
+                }
 
                 let ctx = RequestContext {
                     app_data: data,
                     req,
                     params,
                     endpoint,
-                    next_middleware: middleware,
+                    next_middleware: &middleware,
                 };
-                Ok(await!(ctx.next()))
+                let mut res = await!(ctx.next());
+
+                if let Some(allowed) = allowed {
+                    res.headers_mut()
+                        .entry(http::header::ALLOW)
+                        .unwrap()
+                        .or_insert_with(|| allowed.header_value());
+                }
+                Ok(res)
             },
         ))
     }
@@ -74,24 +74,189 @@
 //! ```
 //!
 use futures::future::FutureObj;
-use http::status::StatusCode;
+use futures::prelude::*;
 use http_service::Body;
-use multipart::server::Multipart;
+use ::multipart::server::Multipart as SyncMultipart;
+use std::fmt::Display;
 use std::io::Cursor;
 use std::ops::{Deref, DerefMut};
 
-use crate::{configuration::Store, Extract, IntoResponse, Request, Response, RouteMatch};
+use crate::{
+    configuration::Store,
+    headers::{ContentType, TypedHeader},
+    Extract, IntoResponse, Rejection, RejectionKind, Request, Response, RouteMatch,
+};
 
-// Small utility function to return a stamped error when we cannot parse a request body
-fn mk_err<T>(_: T) -> Response {
-    StatusCode::BAD_REQUEST.into_response()
+mod multipart;
+mod stream;
+mod upload;
+
+pub use self::multipart::{Field, MultipartError, MultipartLimits, MultipartStream, NextField};
+pub use self::stream::{BodyReader, BodyStream};
+pub use self::upload::{FileUploads, Multipart, UploadedFile};
+
+/// Reject a request because its body could not be read.
+fn read_err(extractor: &'static str, err: impl Display) -> Response {
+    let message = format!("Failed to read the request body: {}", err);
+    Rejection::new(RejectionKind::Body, extractor, message).into_response()
+}
+
+/// The maximum size of request bodies read by the extractors of this module, in bytes.
+///
+/// The limit is a configuration item, so it can be set for the whole app with `App::config`, for a
+/// subrouter with `Router::config`, or for a single endpoint with `EndpointData::config`. It
+/// defaults to 2 MiB. Requests with a larger body are answered with `413 Payload Too Large`,
+/// without reading more of the body than the limit.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::body::{self, SizeLimit};
+///
+/// let mut app = tide::App::new(());
+/// app.config(SizeLimit(64 * 1024));
+/// app.at("/upload")
+///     .post(async move |data: body::Bytes| data.len().to_string())
+///     .config(SizeLimit(100 * 1024 * 1024));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SizeLimit(pub u64);
+
+impl Default for SizeLimit {
+    fn default() -> Self {
+        SizeLimit(2 * 1024 * 1024)
+    }
+}
+
+/// Whether `Json` and `Form` check the `Content-Type` of requests.
+///
+/// With the default, `ContentTypeCheck::Enforce`, a `Json` body must be sent as `application/json`
+/// or a `+json` media type such as `application/ld+json`, and a `Form` body as
+/// `application/x-www-form-urlencoded`. Other requests are answered with
+/// `415 Unsupported Media Type`. Like `SizeLimit`, this is a configuration item, so the check can
+/// be relaxed for the endpoints serving legacy clients.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::body::{self, ContentTypeCheck};
+///
+/// let mut app = tide::App::new(());
+/// app.at("/legacy/notes")
+///     .post(async move |body::Json(note): body::Json<String>| note)
+///     .config(ContentTypeCheck::Ignore);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentTypeCheck {
+    /// Reject requests without the expected `Content-Type`. This is the default.
+    Enforce,
+    /// Parse the body regardless of the `Content-Type`.
+    Ignore,
+}
+
+impl Default for ContentTypeCheck {
+    fn default() -> Self {
+        ContentTypeCheck::Enforce
+    }
+}
+
+/// Check that `req` has a content type accepted by `accepts`, unless checks are disabled in
+/// `store`. `expected` describes the accepted types for the rejection.
+fn check_content_type(
+    req: &Request,
+    store: &Store,
+    extractor: &'static str,
+    expected: &str,
+    accepts: impl Fn(&str) -> bool,
+) -> Result<(), Response> {
+    if store.read::<ContentTypeCheck>().cloned().unwrap_or_default() == ContentTypeCheck::Ignore {
+        return Ok(());
+    }
+    let message = match ContentType::from_headers(req.headers()) {
+        Some(ref content_type) if accepts(&content_type.essence()) => return Ok(()),
+        Some(content_type) => format!("Expected {} content, found `{}`", expected, content_type.0),
+        None => format!("Expected {} content, but the content type is missing", expected),
+    };
+    Err(Rejection::new(RejectionKind::UnsupportedMediaType, extractor, message).into_response())
+}
+
+fn is_json(essence: &str) -> bool {
+    essence == "application/json" || (essence.contains('/') && essence.ends_with("+json"))
+}
+
+fn is_form(essence: &str) -> bool {
+    essence == "application/x-www-form-urlencoded"
+}
+
+/// A request body taken out of the request, together with the size limit of the endpoint.
+struct LimitedBody {
+    body: Body,
+    content_length: Option<u64>,
+    limit: u64,
+    extractor: &'static str,
+}
+
+impl LimitedBody {
+    /// Take the body of `req` for reading by `extractor`.
+    fn take(req: &mut Request, store: &Store, extractor: &'static str) -> LimitedBody {
+        let content_length = req
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok())
+            .and_then(|len| len.parse().ok());
+        LimitedBody {
+            body: std::mem::replace(req.body_mut(), Body::empty()),
+            content_length,
+            limit: store.read::<SizeLimit>().cloned().unwrap_or_default().0,
+            extractor,
+        }
+    }
+
+    /// The response for a body exceeding the limit.
+    fn too_large(&self) -> Response {
+        let message = format!("Request body exceeds the limit of {} bytes", self.limit);
+        Rejection::new(RejectionKind::TooLarge, self.extractor, message).into_response()
+    }
+
+    /// Fail if the `Content-Length` of the request already exceeds the limit.
+    fn check_content_length(&self) -> Result<(), Response> {
+        if self.content_length.map_or(false, |len| len > self.limit) {
+            Err(self.too_large())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read the whole body, failing once it exceeds the limit.
+    async fn into_vec(mut self) -> Result<Vec<u8>, Response> {
+        self.check_content_length()?;
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = await!(self.body.next()) {
+            let chunk = chunk.map_err(|e| read_err(self.extractor, e))?;
+            if (bytes.len() + chunk.len()) as u64 > self.limit {
+                return Err(self.too_large());
+            }
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Reject a request because its body could not be parsed as `what`.
+fn parse_err(extractor: &'static str, what: &str, err: impl Display) -> Response {
+    let message = format!("Invalid {}: {}", what, err);
+    Rejection::new(RejectionKind::Malformed, extractor, message).into_response()
 }
 
 /// A wrapper for multipart form
 ///
 /// This type is useable as an extractor (argument to an endpoint) for getting
-/// a Multipart type defined in the multipart crate
-pub struct MultipartForm(pub Multipart<Cursor<Vec<u8>>>);
+/// a Multipart type defined in the multipart crate. The whole body is read into memory and parsed
+/// synchronously; prefer `Multipart` or `MultipartStream` for uploads.
+pub struct MultipartForm(pub SyncMultipart<Cursor<Vec<u8>>>);
 
 impl<S: 'static> Extract<S> for MultipartForm {
     // Note: cannot use `existential type` here due to ICE
@@ -112,13 +277,17 @@ impl<S: 'static> Extract<S> for MultipartForm {
             Some(ct[idx + BOUNDARY.len()..].to_string())
         });
 
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = LimitedBody::take(req, store, "body::MultipartForm");
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
-                let boundary = boundary.ok_or(()).map_err(mk_err)?;
-                let mp = Multipart::with_body(Cursor::new(body), boundary);
+                let body = await!(body.into_vec())?;
+                let boundary = boundary.ok_or_else(|| {
+                    let message = "Missing multipart boundary in the `content-type` header";
+                    Rejection::new(RejectionKind::Missing, "body::MultipartForm", message)
+                        .into_response()
+                })?;
+                let mp = SyncMultipart::with_body(Cursor::new(body), boundary);
                 Ok(MultipartForm(mp))
             },
         ))
@@ -126,14 +295,14 @@ impl<S: 'static> Extract<S> for MultipartForm {
 }
 
 impl Deref for MultipartForm {
-    type Target = Multipart<Cursor<Vec<u8>>>;
-    fn deref(&self) -> &Multipart<Cursor<Vec<u8>>> {
+    type Target = SyncMultipart<Cursor<Vec<u8>>>;
+    fn deref(&self) -> &SyncMultipart<Cursor<Vec<u8>>> {
         &self.0
     }
 }
 
 impl DerefMut for MultipartForm {
-    fn deref_mut(&mut self) -> &mut Multipart<Cursor<Vec<u8>>> {
+    fn deref_mut(&mut self) -> &mut SyncMultipart<Cursor<Vec<u8>>> {
         &mut self.0
     }
 }
@@ -142,6 +311,10 @@ impl DerefMut for MultipartForm {
 ///
 /// This type is usable both as an extractor (argument to an endpoint) and as a response
 /// (return value from an endpoint).
+///
+/// An empty request body is rejected as missing, so that an `Option<Json<T>>` extractor yields
+/// `None` for it. Requests are checked for a JSON `Content-Type`, see `ContentTypeCheck`, except
+/// that an empty body without any `Content-Type` still counts as missing.
 pub struct Json<T>(pub T);
 
 impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for Json<T> {
@@ -154,11 +327,28 @@ impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        // Without a content type, an empty body is rejected as missing rather than unsupported.
+        let untyped = ContentType::from_headers(req.headers()).is_none();
+        let (content_type, untyped_rejection) =
+            match check_content_type(req, store, "body::Json", "JSON", is_json) {
+                Err(resp) if untyped => (Ok(()), Some(resp)),
+                result => (result, None),
+            };
+        let body = LimitedBody::take(req, store, "body::Json");
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
-                let json: T = serde_json::from_slice(&body).map_err(mk_err)?;
+                content_type?;
+                let body = await!(body.into_vec())?;
+                if body.is_empty() {
+                    let message = "Missing JSON request body";
+                    return Err(Rejection::new(RejectionKind::Missing, "body::Json", message)
+                        .into_response());
+                }
+                if let Some(resp) = untyped_rejection {
+                    return Err(resp);
+                }
+                let json: T = serde_json::from_slice(&body)
+                    .map_err(|e| parse_err("body::Json", "JSON body", e))?;
                 Ok(Json(json))
             },
         ))
@@ -194,6 +384,9 @@ impl<T> DerefMut for Json<T> {
 /// This type is usable both as an extractor (argument to an endpoint) and as a response
 /// (return value from an endpoint), though returning a response with form data is uncommon
 /// and probably not good practice.
+///
+/// Requests are checked for the `application/x-www-form-urlencoded` content type, see
+/// `ContentTypeCheck`.
 pub struct Form<T>(pub T);
 
 impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for Form<T> {
@@ -206,11 +399,14 @@ impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let content_type = check_content_type(req, store, "body::Form", "form", is_form);
+        let body = LimitedBody::take(req, store, "body::Form");
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
-                let data: T = serde_qs::from_bytes(&body).map_err(mk_err)?;
+                content_type?;
+                let body = await!(body.into_vec())?;
+                let data: T = serde_qs::from_bytes(&body)
+                    .map_err(|e| parse_err("body::Form", "form body", e))?;
                 Ok(Form(data))
             },
         ))
@@ -254,12 +450,13 @@ impl<S: 'static> Extract<S> for Str {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = LimitedBody::take(req, store, "body::Str");
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
-                let string = String::from_utf8(body).map_err(mk_err)?;
+                let body = await!(body.into_vec())?;
+                let string = String::from_utf8(body)
+                    .map_err(|e| parse_err("body::Str", "UTF-8 body", e))?;
                 Ok(Str(string))
             },
         ))
@@ -290,11 +487,11 @@ impl<S: 'static> Extract<S> for StrLossy {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = LimitedBody::take(req, store, "body::StrLossy");
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body.into_vec())?;
                 let string = String::from_utf8_lossy(&body).to_string();
                 Ok(StrLossy(string))
             },
@@ -326,11 +523,11 @@ impl<S: 'static> Extract<S> for Bytes {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = LimitedBody::take(req, store, "body::Bytes");
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body.into_vec())?;
                 Ok(Bytes(body))
             },
         ))
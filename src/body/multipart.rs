@@ -0,0 +1,604 @@
+use bytes::{Bytes, BytesMut};
+use futures::future;
+use futures::prelude::*;
+use futures::task::Waker;
+use futures::Poll;
+use http_service::Body;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+
+use crate::{
+    configuration::Store,
+    headers::{ContentType, TypedHeader},
+    Extract, IntoResponse, Rejection, RejectionKind, Request, Response, RouteMatch,
+};
+
+/// The maximum size of the header block of a single part.
+const MAX_PART_HEADERS: usize = 16 * 1024;
+
+/// Size limits for multipart bodies, in bytes.
+///
+/// The limits are a configuration item, set like `SizeLimit`, which does not apply to multipart
+/// bodies. They default to 8 MiB per field and 16 MiB for the whole body.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::body::{MultipartLimits, MultipartStream};
+///
+/// let mut app = tide::App::new(());
+/// app.at("/avatar")
+///     .post(async move |form: MultipartStream| "uploaded")
+///     .config(MultipartLimits {
+///         field: 1024 * 1024,
+///         total: 1024 * 1024 + 4096,
+///     });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultipartLimits {
+    /// The maximum size of the content of a single field.
+    pub field: u64,
+    /// The maximum size of the whole body, including the headers and boundaries of all parts.
+    pub total: u64,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        MultipartLimits {
+            field: 8 * 1024 * 1024,
+            total: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// An error reading a multipart body.
+#[derive(Debug)]
+pub enum MultipartError {
+    /// The request body could not be read.
+    Io(io::Error),
+    /// The body is not valid `multipart/form-data`.
+    Malformed(String),
+    /// The field with the given name exceeds `MultipartLimits::field`.
+    FieldTooLarge(String),
+    /// The body exceeds `MultipartLimits::total`.
+    TooLarge,
+}
+
+impl MultipartError {
+    fn malformed(message: &str) -> MultipartError {
+        MultipartError::Malformed(message.to_owned())
+    }
+
+    /// Convert the error into a rejection by `extractor`.
+    pub fn into_rejection(self, extractor: &'static str) -> Rejection {
+        let kind = match self {
+            MultipartError::Io(_) => RejectionKind::Body,
+            MultipartError::Malformed(_) => RejectionKind::Malformed,
+            MultipartError::FieldTooLarge(_) | MultipartError::TooLarge => RejectionKind::TooLarge,
+        };
+        Rejection::new(kind, extractor, self.to_string())
+    }
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultipartError::Io(err) => write!(f, "Failed to read the request body: {}", err),
+            MultipartError::Malformed(message) => write!(f, "Invalid multipart body: {}", message),
+            MultipartError::FieldTooLarge(name) => {
+                write!(f, "Multipart field `{}` exceeds the size limit", name)
+            }
+            MultipartError::TooLarge => f.write_str("Multipart body exceeds the size limit"),
+        }
+    }
+}
+
+impl std::error::Error for MultipartError {}
+
+impl IntoResponse for MultipartError {
+    fn into_response(self) -> Response {
+        self.into_rejection("body::MultipartStream").into_response()
+    }
+}
+
+macro_rules! try_poll {
+    ($e:expr) => {
+        match $e {
+            Poll::Ready(Ok(t)) => t,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        }
+    };
+}
+
+/// Where the parser is within the body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Before the first delimiter.
+    Preamble,
+    /// Right after a delimiter, which is either followed by a part or ends the body.
+    Boundary,
+    /// In the header block of a part.
+    Headers,
+    /// In the content of a part.
+    Content,
+    /// After the closing delimiter.
+    End,
+}
+
+/// The headers of a part.
+struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+}
+
+/// An extractor for reading a `multipart/form-data` body field by field.
+///
+/// The body is parsed as it arrives, and the content of each field is streamed, so that uploads
+/// are never buffered as a whole. Fields must be read in order: asking for the next field skips
+/// the rest of the current one. The size of fields and of the whole body are bounded by the
+/// `MultipartLimits` of the endpoint, where skipped content only counts towards the total.
+///
+/// Requests without a multipart content type are answered with `415 Unsupported Media Type`.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api, await_macro)]
+/// use futures::prelude::*;
+/// use tide::body::{MultipartError, MultipartStream};
+///
+/// async fn upload(mut form: MultipartStream) -> Result<String, MultipartError> {
+///     let mut summary = String::new();
+///     while let Some(mut field) = await!(form.next_field())? {
+///         let mut len = 0;
+///         while let Some(chunk) = await!(field.next()) {
+///             len += chunk?.len();
+///         }
+///         summary += &format!("{} ({:?}): {} bytes\n", field.name(), field.filename(), len);
+///     }
+///     Ok(summary)
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/upload").post(upload);
+///     app.serve()
+/// }
+/// ```
+pub struct MultipartStream {
+    body: Body,
+    /// The delimiter preceding each part, `\r\n--` followed by the boundary.
+    delimiter: Vec<u8>,
+    buf: BytesMut,
+    state: State,
+    limits: MultipartLimits,
+    /// The number of bytes read from the body.
+    total: u64,
+    /// The number of content bytes of the current field.
+    field: u64,
+    /// The size limit of the current field.
+    field_limit: u64,
+    /// The name of the current field, for errors.
+    field_name: String,
+}
+
+impl MultipartStream {
+    /// Take the multipart body of `req` for reading by `extractor`.
+    pub(crate) fn from_request(
+        req: &mut Request,
+        store: &Store,
+        extractor: &'static str,
+    ) -> Result<MultipartStream, Response> {
+        let content_type = ContentType::from_headers(req.headers())
+            .filter(|content_type| content_type.essence().starts_with("multipart/"));
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => {
+                let message = "Expected multipart content";
+                let kind = RejectionKind::UnsupportedMediaType;
+                return Err(Rejection::new(kind, extractor, message).into_response());
+            }
+        };
+        let boundary = match content_type.param("boundary") {
+            Some(boundary) if !boundary.is_empty() => boundary,
+            _ => {
+                let message = "Missing multipart boundary in the `content-type` header";
+                let kind = RejectionKind::Malformed;
+                return Err(Rejection::new(kind, extractor, message).into_response());
+            }
+        };
+
+        let limits = store.read::<MultipartLimits>().cloned().unwrap_or_default();
+        let content_length = req
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok())
+            .and_then(|len| len.parse::<u64>().ok());
+        if content_length.map_or(false, |len| len > limits.total) {
+            return Err(MultipartError::TooLarge
+                .into_rejection(extractor)
+                .into_response());
+        }
+
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        // The first delimiter is not preceded by a line break, so pretend there is one.
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"\r\n");
+
+        Ok(MultipartStream {
+            body: std::mem::replace(req.body_mut(), Body::empty()),
+            delimiter,
+            buf,
+            state: State::Preamble,
+            limits,
+            total: 0,
+            field: 0,
+            field_limit: limits.field,
+            field_name: String::new(),
+        })
+    }
+
+    /// The next field, or `None` after the last one.
+    pub fn next_field(&mut self) -> NextField<'_> {
+        NextField {
+            multipart: Some(self),
+        }
+    }
+
+    /// Read more of the body into the buffer.
+    ///
+    /// The end of the body is an error, since the parser stops reading at the closing delimiter.
+    fn poll_fill(&mut self, waker: &Waker) -> Poll<Result<(), MultipartError>> {
+        match Pin::new(&mut self.body).poll_next(waker) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                self.total += chunk.len() as u64;
+                if self.total > self.limits.total {
+                    return Poll::Ready(Err(MultipartError::TooLarge));
+                }
+                self.buf.extend_from_slice(&chunk);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Err(MultipartError::Io(err))),
+            Poll::Ready(None) => Poll::Ready(Err(MultipartError::malformed(
+                "unexpected end of the body",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Advance to the headers of the next part, skipping the rest of the current one.
+    fn poll_next_part(&mut self, waker: &Waker) -> Poll<Result<Option<Part>, MultipartError>> {
+        loop {
+            match self.state {
+                State::Preamble => match find(&self.buf, &self.delimiter) {
+                    Some(idx) => {
+                        self.buf.advance(idx + self.delimiter.len());
+                        self.state = State::Boundary;
+                    }
+                    None => {
+                        // Keep what could be the start of the delimiter.
+                        let skip = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+                        self.buf.advance(skip);
+                        try_poll!(self.poll_fill(waker));
+                    }
+                },
+                State::Boundary => {
+                    // Skip the transport padding allowed after a delimiter.
+                    let padding = self
+                        .buf
+                        .iter()
+                        .take_while(|&&byte| byte == b' ' || byte == b'\t')
+                        .count();
+                    self.buf.advance(padding);
+                    if self.buf.len() < 2 {
+                        try_poll!(self.poll_fill(waker));
+                    } else if self.buf.starts_with(b"--") {
+                        self.state = State::End;
+                    } else if self.buf.starts_with(b"\r\n") {
+                        self.buf.advance(2);
+                        self.state = State::Headers;
+                    } else {
+                        return Poll::Ready(Err(MultipartError::malformed(
+                            "invalid characters after a boundary",
+                        )));
+                    }
+                }
+                State::Headers => {
+                    let end = if self.buf.starts_with(b"\r\n") {
+                        Some((0, 2))
+                    } else {
+                        find(&self.buf, b"\r\n\r\n").map(|idx| (idx, idx + 4))
+                    };
+                    match end {
+                        Some((end, len)) => {
+                            let block = self.buf.split_to(len);
+                            let part = match parse_part(&block[..end]) {
+                                Ok(part) => part,
+                                Err(err) => return Poll::Ready(Err(err)),
+                            };
+                            self.state = State::Content;
+                            self.field = 0;
+                            self.field_limit = self.limits.field;
+                            self.field_name = part.name.clone();
+                            return Poll::Ready(Ok(Some(part)));
+                        }
+                        None if self.buf.len() > MAX_PART_HEADERS => {
+                            return Poll::Ready(Err(MultipartError::malformed(
+                                "part headers too large",
+                            )));
+                        }
+                        None => try_poll!(self.poll_fill(waker)),
+                    }
+                }
+                State::Content => {
+                    // The rest of the field is discarded, so only the total limit applies.
+                    self.field_limit = u64::max_value();
+                    try_poll!(self.poll_content(waker));
+                }
+                State::End => return Poll::Ready(Ok(None)),
+            }
+        }
+    }
+
+    /// Read the next chunk of the content of the current part, or `None` at its end.
+    fn poll_content(&mut self, waker: &Waker) -> Poll<Result<Option<Bytes>, MultipartError>> {
+        loop {
+            if self.state != State::Content {
+                return Poll::Ready(Ok(None));
+            }
+            let len = match find(&self.buf, &self.delimiter) {
+                Some(0) => {
+                    self.buf.advance(self.delimiter.len());
+                    self.state = State::Boundary;
+                    return Poll::Ready(Ok(None));
+                }
+                Some(idx) => idx,
+                // Everything but what could be the start of the delimiter is content.
+                None => self.buf.len().saturating_sub(self.delimiter.len() - 1),
+            };
+            if len == 0 {
+                try_poll!(self.poll_fill(waker));
+                continue;
+            }
+            self.field += len as u64;
+            if self.field > self.field_limit {
+                let name = self.field_name.clone();
+                return Poll::Ready(Err(MultipartError::FieldTooLarge(name)));
+            }
+            return Poll::Ready(Ok(Some(self.buf.split_to(len).freeze())));
+        }
+    }
+}
+
+impl<S: 'static> Extract<S> for MultipartStream {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        future::ready(MultipartStream::from_request(
+            req,
+            store,
+            "body::MultipartStream",
+        ))
+    }
+}
+
+/// The future returned by `MultipartStream::next_field`.
+pub struct NextField<'a> {
+    multipart: Option<&'a mut MultipartStream>,
+}
+
+impl<'a> Future for NextField<'a> {
+    type Output = Result<Option<Field<'a>>, MultipartError>;
+
+    fn poll(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Self::Output> {
+        let multipart = self
+            .multipart
+            .as_mut()
+            .expect("NextField polled after completion");
+        let part = match try_poll!(multipart.poll_next_part(waker)) {
+            Some(part) => part,
+            None => return Poll::Ready(Ok(None)),
+        };
+        let multipart = self.multipart.take().unwrap();
+        Poll::Ready(Ok(Some(Field { multipart, part })))
+    }
+}
+
+/// A field of a multipart body.
+///
+/// The field is a `Stream` of the chunks of its content.
+pub struct Field<'a> {
+    multipart: &'a mut MultipartStream,
+    part: Part,
+}
+
+impl<'a> Field<'a> {
+    /// The name of the field.
+    pub fn name(&self) -> &str {
+        &self.part.name
+    }
+
+    /// The name of the uploaded file, if the field is a file.
+    pub fn filename(&self) -> Option<&str> {
+        self.part.filename.as_ref().map(|filename| &**filename)
+    }
+
+    /// The media type of the content, if given.
+    pub fn content_type(&self) -> Option<&str> {
+        self.part.content_type.as_ref().map(|mime| &**mime)
+    }
+
+    /// Replace the size limit of this field, e.g. for file uploads.
+    pub(crate) fn set_limit(&mut self, limit: u64) {
+        self.multipart.field_limit = limit;
+    }
+
+    /// Read the whole content of the field.
+    pub fn bytes(mut self) -> impl Future<Output = Result<Vec<u8>, MultipartError>> + 'a {
+        async move {
+            let mut bytes = Vec::new();
+            while let Some(chunk) = await!(self.next()) {
+                bytes.extend_from_slice(&chunk?);
+            }
+            Ok(bytes)
+        }
+    }
+
+    /// Read the whole content of the field as UTF-8 text.
+    pub fn text(self) -> impl Future<Output = Result<String, MultipartError>> + 'a {
+        let name = self.part.name.clone();
+        self.bytes().map(move |bytes| {
+            String::from_utf8(bytes?).map_err(|_| {
+                MultipartError::Malformed(format!("field `{}` is not valid UTF-8", name))
+            })
+        })
+    }
+}
+
+impl<'a> Stream for Field<'a> {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Option<Self::Item>> {
+        match self.multipart.poll_content(waker) {
+            Poll::Ready(Ok(Some(chunk))) => Poll::Ready(Some(Ok(chunk))),
+            Poll::Ready(Ok(None)) => Poll::Ready(None),
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Parse the header block of a part.
+fn parse_part(block: &[u8]) -> Result<Part, MultipartError> {
+    let block = std::str::from_utf8(block)
+        .map_err(|_| MultipartError::malformed("part headers are not valid UTF-8"))?;
+
+    let mut disposition = None;
+    let mut content_type = None;
+    for line in block.split("\r\n").filter(|line| !line.is_empty()) {
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap().trim();
+        let value = parts
+            .next()
+            .ok_or_else(|| MultipartError::malformed("invalid part header"))?
+            .trim();
+        if name.eq_ignore_ascii_case("content-disposition") {
+            disposition = Some(value);
+        } else if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.to_owned());
+        }
+    }
+
+    let params = disposition
+        .map(parse_params)
+        .ok_or_else(|| MultipartError::malformed("part without a content disposition"))?;
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    Ok(Part {
+        name: param("name").ok_or_else(|| MultipartError::malformed("part without a name"))?,
+        filename: param("filename"),
+        content_type,
+    })
+}
+
+/// Parse the parameters of a header value such as `form-data; name="a"; filename="b.txt"`.
+fn parse_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = match value.find(';') {
+        Some(idx) => &value[idx + 1..],
+        None => return params,
+    };
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim().to_ascii_lowercase();
+        rest = rest[eq + 1..].trim_start();
+        let mut value = String::new();
+        if rest.starts_with('"') {
+            let mut end = rest.len();
+            let mut escaped = false;
+            for (idx, c) in rest.char_indices().skip(1) {
+                if escaped {
+                    // Only quotes and backslashes are escaped, keep Windows paths intact.
+                    if c != '"' && c != '\\' {
+                        value.push('\\');
+                    }
+                    value.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    end = idx + 1;
+                    break;
+                } else {
+                    value.push(c);
+                }
+            }
+            rest = &rest[end..];
+        } else {
+            let end = rest.find(';').unwrap_or_else(|| rest.len());
+            value.push_str(rest[..end].trim());
+            rest = &rest[end..];
+        }
+        params.push((name, value));
+        match rest.find(';') {
+            Some(idx) => rest = &rest[idx + 1..],
+            None => break,
+        }
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_disposition() {
+        let params = parse_params(r#"form-data; name="file"; filename="C:\docs\a \"b\";c.txt""#);
+        assert_eq!(
+            params,
+            vec![
+                ("name".to_owned(), "file".to_owned()),
+                ("filename".to_owned(), r#"C:\docs\a "b";c.txt"#.to_owned()),
+            ]
+        );
+        assert_eq!(
+            parse_params("form-data; Name=plain"),
+            vec![("name".to_owned(), "plain".to_owned())]
+        );
+    }
+
+    #[test]
+    fn part_headers() {
+        let part = parse_part(
+            b"Content-Disposition: form-data; name=\"avatar\"; filename=\"me.png\"\r\n\
+              Content-Type: image/png",
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(part.name, "avatar");
+        assert_eq!(part.filename.as_ref().map(|f| &**f), Some("me.png"));
+        assert_eq!(part.content_type.as_ref().map(|f| &**f), Some("image/png"));
+
+        assert!(parse_part(b"Content-Type: text/plain").is_err());
+    }
+}
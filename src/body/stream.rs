@@ -0,0 +1,122 @@
+use futures::future;
+use futures::io::AsyncRead;
+use futures::prelude::*;
+use futures::task::Waker;
+use futures::Poll;
+use http_service::Body;
+use std::io;
+use std::pin::Pin;
+
+use super::LimitedBody;
+use crate::{configuration::Store, Extract, Request, Response, RouteMatch};
+
+/// An extractor for streaming the request body.
+///
+/// Unlike the other extractors of this module, `BodyStream` does not buffer the body, but yields
+/// it chunk by chunk as a `Stream`. Use `into_reader` to read it through `AsyncRead` instead.
+///
+/// The `SizeLimit` of the endpoint still applies: a request whose `Content-Length` exceeds the
+/// limit is answered with `413 Payload Too Large` right away, and a body exceeding the limit while
+/// streaming ends with an error of kind `InvalidData`. Raise the limit for endpoints accepting
+/// large uploads.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api, await_macro)]
+/// use futures::prelude::*;
+/// use tide::body::{BodyStream, SizeLimit};
+///
+/// async fn upload(mut body: BodyStream) -> Result<String, http::StatusCode> {
+///     let mut len = 0;
+///     while let Some(chunk) = await!(body.next()) {
+///         let chunk = chunk.map_err(|_| http::StatusCode::BAD_REQUEST)?;
+///         len += chunk.len();
+///     }
+///     Ok(format!("received {} bytes", len))
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/upload").post(upload).config(SizeLimit(1 << 30));
+///     app.serve()
+/// }
+/// ```
+pub struct BodyStream {
+    body: Body,
+    remaining: u64,
+}
+
+impl BodyStream {
+    /// Read the body through `AsyncRead`.
+    pub fn into_reader(self) -> BodyReader {
+        BodyReader {
+            stream: self,
+            chunk: bytes::Bytes::new(),
+        }
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = Result<bytes::Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Option<Self::Item>> {
+        let chunk = match Pin::new(&mut self.body).poll_next(waker) {
+            Poll::Ready(Some(Ok(chunk))) => chunk,
+            other => return other,
+        };
+        if chunk.len() as u64 > self.remaining {
+            // End the stream after the error.
+            self.body = Body::empty();
+            return Poll::Ready(Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request body exceeds the size limit",
+            ))));
+        }
+        self.remaining -= chunk.len() as u64;
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+impl<S: 'static> Extract<S> for BodyStream {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let body = LimitedBody::take(req, store, "body::BodyStream");
+        if let Err(resp) = body.check_content_length() {
+            return future::err(resp);
+        }
+        future::ok(BodyStream {
+            body: body.body,
+            remaining: body.limit,
+        })
+    }
+}
+
+/// An `AsyncRead` adapter for a `BodyStream`.
+pub struct BodyReader {
+    stream: BodyStream,
+    /// The unread rest of the last chunk.
+    chunk: bytes::Bytes,
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(&mut self, waker: &Waker, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        while self.chunk.is_empty() {
+            match Pin::new(&mut self.stream).poll_next(waker) {
+                Poll::Ready(Some(Ok(chunk))) => self.chunk = chunk,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Poll::Ready(Ok(len))
+    }
+}
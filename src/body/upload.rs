@@ -0,0 +1,371 @@
+use futures::channel::oneshot;
+use futures::future::FutureObj;
+use futures::prelude::*;
+use lazy_static::lazy_static;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::collections::hash_map::{HashMap, RandomState};
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{self, Arc, Mutex};
+use tempfile::TempPath;
+
+use super::{parse_err, MultipartError, MultipartStream};
+use crate::{
+    configuration::Store,
+    fields::{self, Node},
+    params::Error,
+    Extract, IntoResponse, Rejection, RejectionKind, Request, Response, RouteMatch,
+};
+
+const EXTRACTOR: &str = "body::Multipart";
+
+/// The number of threads writing uploaded files.
+const WRITER_THREADS: usize = 4;
+
+/// The error of `rename` when moving a file to another file system.
+#[cfg(unix)]
+const CROSS_DEVICE: i32 = 18; // EXDEV
+#[cfg(windows)]
+const CROSS_DEVICE: i32 = 17; // ERROR_NOT_SAME_DEVICE
+
+/// A blocking file system call run by a writer thread.
+type Job = Box<dyn FnMut() + Send>;
+
+lazy_static! {
+    /// The files handed from the form deserializer to `UploadedFile::deserialize`, by token.
+    ///
+    /// The deserializer owning a file registers it under a random token only for the duration of
+    /// a single visitor call, so that files can neither be forged by other deserializers nor taken
+    /// by values other than an `UploadedFile`.
+    static ref HANDOFF: Mutex<HashMap<u64, UploadedFile>> = Mutex::new(HashMap::new());
+
+    /// The queue of the writer threads, started on the first upload.
+    static ref WRITERS: Mutex<sync::mpsc::Sender<Job>> = {
+        let (sender, receiver) = sync::mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..WRITER_THREADS {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                let mut job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                // A panicking job only drops the sender of its result.
+                let _ = panic::catch_unwind(AssertUnwindSafe(move || job()));
+            });
+        }
+        Mutex::new(sender)
+    };
+}
+
+/// Where and how large files uploaded through `Multipart` are spooled.
+///
+/// This is a configuration item, set like `MultipartLimits`. Files are written to the system's
+/// temporary directory by default, and may be up to 8 MiB large. Note that `MultipartLimits::total`
+/// still bounds the whole body, so it needs to be raised as well for larger uploads.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::body::{FileUploads, MultipartLimits};
+///
+/// let mut app = tide::App::new(());
+/// app.at("/videos")
+///     .post(async move || "uploaded")
+///     .config(FileUploads {
+///         dir: "/var/spool/videos".into(),
+///         max_size: 1 << 30,
+///     })
+///     .config(MultipartLimits {
+///         field: 64 * 1024,
+///         total: (1 << 30) + (1 << 20),
+///     });
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileUploads {
+    /// The directory the files are written to.
+    pub dir: PathBuf,
+    /// The maximum size of a single file.
+    pub max_size: u64,
+}
+
+impl Default for FileUploads {
+    fn default() -> Self {
+        FileUploads {
+            dir: std::env::temp_dir(),
+            max_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// A file uploaded through a `Multipart` form.
+///
+/// The file is spooled to a temporary file in the `FileUploads` directory, with a random name and
+/// readable only by its owner. It is removed when the `UploadedFile` is dropped unless it was moved
+/// elsewhere with `persist`.
+///
+/// `UploadedFile` can only be deserialized as a field of a `Multipart` form.
+#[derive(Debug)]
+pub struct UploadedFile {
+    path: TempPath,
+    filename: Option<String>,
+    content_type: Option<String>,
+    size: u64,
+}
+
+impl UploadedFile {
+    /// The path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The name of the file given by the client, if any.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_ref().map(|filename| &**filename)
+    }
+
+    /// The media type given by the client, if any.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_ref().map(|mime| &**mime)
+    }
+
+    /// The size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Move the file to `path`, so that it is kept after the upload is dropped.
+    ///
+    /// If `path` is on another file system, the file is copied instead.
+    pub fn persist(self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        match self.path.persist(path) {
+            Ok(()) => Ok(()),
+            // The temporary file is removed after copying.
+            Err(err) if err.error.raw_os_error() == Some(CROSS_DEVICE) => {
+                fs::copy(&err.path, path).map(|_| ())
+            }
+            Err(err) => Err(err.error),
+        }
+    }
+}
+
+impl UploadedFile {
+    /// The name under which `UploadedFile` asks the form deserializer for a file.
+    pub(crate) const MARKER: &'static str = "$tide::UploadedFile";
+
+    /// Hand the file to `visitor`, which takes it if it deserializes an `UploadedFile`.
+    pub(crate) fn hand_to<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let token = hand_off(self);
+        let result = visitor.visit_u64(token);
+        // Remove the file if the visitor did not take it.
+        HANDOFF.lock().unwrap().remove(&token);
+        result
+    }
+}
+
+impl<'de> Deserialize<'de> for UploadedFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UploadedFileVisitor;
+
+        impl<'de> Visitor<'de> for UploadedFileVisitor {
+            type Value = UploadedFile;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("an uploaded file")
+            }
+
+            fn visit_u64<E: de::Error>(self, token: u64) -> Result<UploadedFile, E> {
+                HANDOFF
+                    .lock()
+                    .unwrap()
+                    .remove(&token)
+                    .ok_or_else(|| E::invalid_type(de::Unexpected::Unsigned(token), &self))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(UploadedFile::MARKER, UploadedFileVisitor)
+    }
+}
+
+/// An extractor for `multipart/form-data` bodies deserialized into `T`.
+///
+/// Fields are deserialized by name, like with `Form`: nested keys (`address[city]`) fill nested
+/// structs, and repeated keys (`tag`, or `tag[]`) or indexed keys (`tag[0]`) fill sequences. Text
+/// fields deserialize into strings and other plain values. File fields are spooled to disk as
+/// configured by `FileUploads` and deserialize only into an `UploadedFile`, or a
+/// `Vec<UploadedFile>` for multiple files of the same name. File inputs left empty by the client
+/// are skipped, so that they fill an `Option<UploadedFile>` with `None`. Files not taken by `T`
+/// are removed after extraction.
+///
+/// The body is streamed through a `MultipartStream`, so `MultipartLimits` applies to text fields
+/// and the whole body. Files are written by a small pool of threads, so that the blocking file
+/// system calls do not stall the executor.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// #[macro_use]
+/// extern crate serde_derive;
+///
+/// use tide::body::{Multipart, UploadedFile};
+///
+/// #[derive(Deserialize)]
+/// struct Post {
+///     title: String,
+///     attachments: Vec<UploadedFile>,
+/// }
+///
+/// async fn create(Multipart(post): Multipart<Post>) -> String {
+///     let names: Vec<_> = post
+///         .attachments
+///         .iter()
+///         .map(|file| file.filename().unwrap_or("unnamed"))
+///         .collect();
+///     format!("{}: {}", post.title, names.join(", "))
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/posts").post(create);
+///     app.serve()
+/// }
+/// ```
+pub struct Multipart<T>(pub T);
+
+impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for Multipart<T> {
+    type Fut = FutureObj<'static, Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let stream = MultipartStream::from_request(req, store, EXTRACTOR);
+        let uploads = store.read::<FileUploads>().cloned().unwrap_or_default();
+        FutureObj::new(Box::new(
+            async move {
+                let mut stream = stream?;
+                let mut fields = Vec::new();
+                while let Some(mut field) = await!(stream.next_field()).map_err(reject)? {
+                    let name = field.name().to_owned();
+                    let filename = match field.filename() {
+                        Some(filename) => filename.to_owned(),
+                        None => {
+                            let text = await!(field.text()).map_err(reject)?;
+                            fields.push((name, Node::Text(text)));
+                            continue;
+                        }
+                    };
+                    let content_type = field.content_type().map(str::to_owned);
+
+                    field.set_limit(uploads.max_size);
+                    let dir = uploads.dir.clone();
+                    let mut file = await!(blocking(move || {
+                        tempfile::Builder::new()
+                            .prefix("tide-upload-")
+                            .tempfile_in(dir)
+                    }))
+                    .map_err(spool_err)?;
+                    let mut size = 0;
+                    while let Some(chunk) = await!(field.next()) {
+                        let chunk = chunk.map_err(reject)?;
+                        size += chunk.len() as u64;
+                        file = await!(blocking(move || {
+                            let mut file = file;
+                            file.write_all(&chunk).map(|()| file)
+                        }))
+                        .map_err(spool_err)?;
+                    }
+                    let path = file.into_temp_path();
+
+                    if filename.is_empty() && size == 0 {
+                        // A file input without a selected file.
+                        continue;
+                    }
+                    let file = UploadedFile {
+                        path,
+                        filename: Some(filename).filter(|filename| !filename.is_empty()),
+                        content_type,
+                        size,
+                    };
+                    fields.push((name, Node::File(file)));
+                }
+                deserialize(fields).map(Multipart)
+            },
+        ))
+    }
+}
+
+fn reject(err: MultipartError) -> Response {
+    err.into_rejection(EXTRACTOR).into_response()
+}
+
+fn spool_err(err: io::Error) -> Response {
+    let message = format!("Failed to store uploaded file: {}", err);
+    Rejection::new(RejectionKind::Internal, EXTRACTOR, message).into_response()
+}
+
+/// Run the blocking file system calls `f` on a writer thread.
+///
+/// Each call holds a thread only while it runs, so that slow uploads do not keep others waiting.
+fn blocking<T, F>(f: F) -> impl Future<Output = io::Result<T>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    let (sender, result) = oneshot::channel();
+    let mut call = Some((f, sender));
+    let job: Job = Box::new(move || {
+        if let Some((f, sender)) = call.take() {
+            let _ = sender.send(f());
+        }
+    });
+    let _ = WRITERS.lock().unwrap().send(job);
+    result.map(|result| {
+        result.unwrap_or_else(|_| {
+            let message = "the writing thread panicked";
+            Err(io::Error::new(io::ErrorKind::Other, message))
+        })
+    })
+}
+
+/// Register `file` for `UploadedFile::deserialize` under a new random token.
+fn hand_off(file: UploadedFile) -> u64 {
+    let mut files = HANDOFF.lock().unwrap();
+    loop {
+        let token = RandomState::new().build_hasher().finish();
+        if !files.contains_key(&token) {
+            files.insert(token, file);
+            return token;
+        }
+    }
+}
+
+/// Deserialize the fields of a form into `T`.
+fn deserialize<T>(fields: Vec<(String, Node)>) -> Result<T, Response>
+where
+    T: serde::de::DeserializeOwned,
+{
+    fields::deserialize(fields).map_err(|err| parse_err(EXTRACTOR, "multipart form", err))
+}
+
+impl<T> Deref for Multipart<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Multipart<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
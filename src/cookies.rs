@@ -1,7 +1,10 @@
 use cookie::{Cookie, CookieJar, ParseError};
 use futures::future;
 
-use crate::{configuration::Store, response::IntoResponse, Extract, Request, Response, RouteMatch};
+use crate::{
+    configuration::Store, response::IntoResponse, Extract, Rejection, RejectionKind, Request,
+    Response, RouteMatch,
+};
 
 /// A representation of cookies which wraps `CookieJar` from `cookie` crate
 ///
@@ -36,7 +39,10 @@ impl<S: 'static> Extract<S> for Cookies {
         };
         let resp = cookie_jar
             .map(|c| Cookies { content: c })
-            .map_err(|_e| http::status::StatusCode::BAD_REQUEST.into_response());
+            .map_err(|e| {
+                let message = format!("Invalid cookie header: {}", e);
+                Rejection::new(RejectionKind::Malformed, "Cookies", message).into_response()
+            });
 
         future::ready(resp)
     }
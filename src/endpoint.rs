@@ -1,7 +1,9 @@
 use futures::future::{Future, FutureObj};
+use std::sync::Arc;
 
 use crate::{
     configuration::Store, Extract, ExtractSeed, head::Head, IntoResponse, Request, Response, RouteMatch,
+    rejection::{self, RenderRejection},
 };
 
 /// The raw representation of an endpoint.
@@ -80,7 +82,15 @@ type BoxedEndpointFn<Data> =
     dyn Fn(Data, Request, Option<RouteMatch>, &Store) -> FutureObj<'static, Response> + Send + Sync;
 
 pub(crate) struct BoxedEndpoint<Data> {
-    endpoint: Box<BoxedEndpointFn<Data>>,
+    endpoint: Arc<BoxedEndpointFn<Data>>,
+}
+
+impl<Data> Clone for BoxedEndpoint<Data> {
+    fn clone(&self) -> Self {
+        BoxedEndpoint {
+            endpoint: self.endpoint.clone(),
+        }
+    }
 }
 
 impl<Data> BoxedEndpoint<Data> {
@@ -89,7 +99,7 @@ impl<Data> BoxedEndpoint<Data> {
         T: Endpoint<Data, Kind>,
     {
         BoxedEndpoint {
-            endpoint: Box::new(move |data, request, params, store| {
+            endpoint: Arc::new(move |data, request, params, store| {
                 FutureObj::new(Box::new(ep.call(data, request, params, store)))
             }),
         }
@@ -141,12 +151,13 @@ macro_rules! seeded_end_point_impl_raw {
                 let f = self.0.clone();
                 let ($($Y),*) = &self.1;
                 $(let $X = <$Y as ExtractSeed<$X, Data>>::extract($Y, &mut data, &mut req, &params, store);)*
+                let renderer = store.read::<RenderRejection>().cloned();
                 FutureObj::new(Box::new(async move {
                     let (parts, _) = req.into_parts();
                     let head = Head::from(parts);
                     $(let $X = match await!($X) {
                         Ok(x) => x,
-                        Err(resp) => return resp,
+                        Err(resp) => return rejection::render(resp, renderer.as_ref()),
                     };)*
                     let res = await!(call_f!($($head;)* (f, head); $($X),*));
 
@@ -176,12 +187,13 @@ macro_rules! end_point_impl_raw {
             fn call(&self, mut data: Data, mut req: Request, params: Option<RouteMatch<'_>>, store: &Store) -> Self::Fut {
                 let f = self.clone();
                 $(let $X = <$X as Extract<Data>>::extract(&mut data, &mut req, &params, store);)*
+                let renderer = store.read::<RenderRejection>().cloned();
                 FutureObj::new(Box::new(async move {
                     let (parts, _) = req.into_parts();
                     let head = Head::from(parts);
                     $(let $X = match await!($X) {
                         Ok(x) => x,
-                        Err(resp) => return resp,
+                        Err(resp) => return rejection::render(resp, renderer.as_ref()),
                     };)*
                     let res = await!(call_f!($($head;)* (f, head); $($X),*));
 
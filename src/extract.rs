@@ -1,6 +1,7 @@
+use futures::future::FutureObj;
 use futures::prelude::*;
 
-use crate::{configuration::Store, Request, Response, RouteMatch};
+use crate::{configuration::Store, Rejection, RejectionKind, Request, Response, RouteMatch};
 
 /// An extractor for an app with `Data`
 pub trait Extract<Data>: Send + Sized + 'static {
@@ -50,3 +51,75 @@ impl<Param, Data> ExtractSeed<Param, Data> for ()
         Param::extract(data, req, params, store)
     }
 }
+
+/// Extract `E` if it is present.
+///
+/// Yields `None` if `E` fails with a `Rejection` of kind `RejectionKind::Missing`, such as a
+/// missing header or an empty JSON body. Other failures, e.g. a malformed header, still cause the
+/// request to be rejected.
+impl<Data, E> Extract<Data> for Option<E>
+where
+    Data: 'static,
+    E: Extract<Data>,
+{
+    type Fut = FutureObj<'static, Result<Self, Response>>;
+
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let fut = E::extract(data, req, params, store);
+        FutureObj::new(Box::new(
+            async move {
+                match await!(fut) {
+                    Ok(e) => Ok(Some(e)),
+                    Err(resp) => {
+                        let missing = resp
+                            .extensions()
+                            .get::<Rejection>()
+                            .map_or(false, |rejection| rejection.kind() == RejectionKind::Missing);
+                        if missing {
+                            Ok(None)
+                        } else {
+                            Err(resp)
+                        }
+                    }
+                }
+            },
+        ))
+    }
+}
+
+/// Extract `E`, handing its rejection to the endpoint instead of responding with it.
+///
+/// Failures that are not a `Rejection`, i.e. plain error responses of custom extractors, still
+/// cause the request to be rejected.
+impl<Data, E> Extract<Data> for Result<E, Rejection>
+where
+    Data: 'static,
+    E: Extract<Data>,
+{
+    type Fut = FutureObj<'static, Result<Self, Response>>;
+
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let fut = E::extract(data, req, params, store);
+        FutureObj::new(Box::new(
+            async move {
+                match await!(fut) {
+                    Ok(e) => Ok(Ok(e)),
+                    Err(mut resp) => match resp.extensions_mut().remove::<Rejection>() {
+                        Some(rejection) => Ok(Err(rejection)),
+                        None => Err(resp),
+                    },
+                }
+            },
+        ))
+    }
+}
@@ -0,0 +1,382 @@
+//! Deserialization of form fields and query strings with serde.
+//!
+//! Fields are named like `address[city]` or `tag[]`: nested keys fill nested structs, and repeated
+//! or indexed keys fill sequences. A single value also deserializes into a sequence of one, so that
+//! `tag=a` fills a `Vec` just like `tag=a&tag=b`.
+
+use serde::de::{self, Error as _, IntoDeserializer, Visitor};
+
+use crate::{body::UploadedFile, params::Error};
+
+/// Deserialize the fields `(name, value)` of a form or query string into `T`.
+pub(crate) fn deserialize<T>(fields: Vec<(String, Node)>) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    let mut entries = Vec::new();
+    for (name, node) in fields {
+        insert(&mut entries, &name, &key_path(&name), node)?;
+    }
+    T::deserialize(NodeDeserializer {
+        name: String::new(),
+        node: Node::Map(entries),
+    })
+}
+
+/// The value of a field, or the fields nested below a key.
+pub(crate) enum Node {
+    Text(String),
+    File(UploadedFile),
+    /// The values of a repeated key.
+    Seq(Vec<Node>),
+    /// The nested keys below a key, in order of their first appearance.
+    Map(Vec<(String, Node)>),
+}
+
+/// Split a field name such as `address[city]` into its keys. A trailing `[]` only marks the key as
+/// repeated, and names which are not of this form are a single key.
+fn key_path(name: &str) -> Vec<&str> {
+    let mut keys = match name.find('[') {
+        Some(idx) if idx > 0 => vec![&name[..idx]],
+        _ => return vec![name],
+    };
+    let mut rest = &name[keys[0].len()..];
+    while rest.starts_with('[') {
+        match rest.find(']') {
+            Some(end) => {
+                keys.push(&rest[1..end]);
+                rest = &rest[end + 1..];
+            }
+            None => return vec![name],
+        }
+    }
+    if !rest.is_empty() {
+        return vec![name];
+    }
+    if keys.last() == Some(&"") {
+        keys.pop();
+    }
+    keys
+}
+
+/// Insert the value of the field `name` at `keys` below `entries`.
+fn insert(
+    entries: &mut Vec<(String, Node)>,
+    name: &str,
+    keys: &[&str],
+    value: Node,
+) -> Result<(), Error> {
+    let (key, rest) = keys.split_first().expect("a field name has at least one key");
+    let idx = entries.iter().position(|(existing, _)| existing == key);
+    if rest.is_empty() {
+        let node = match idx {
+            Some(idx) => &mut entries[idx].1,
+            None => {
+                entries.push((key.to_string(), value));
+                return Ok(());
+            }
+        };
+        match node {
+            Node::Seq(values) => values.push(value),
+            Node::Map(_) => {
+                let message = format!("field `{}` conflicts with nested keys", name);
+                return Err(Error::custom(message));
+            }
+            single => {
+                let first = std::mem::replace(single, Node::Seq(Vec::new()));
+                *single = Node::Seq(vec![first, value]);
+            }
+        }
+        return Ok(());
+    }
+
+    let idx = idx.unwrap_or_else(|| {
+        entries.push((key.to_string(), Node::Map(Vec::new())));
+        entries.len() - 1
+    });
+    match &mut entries[idx].1 {
+        Node::Map(nested) => insert(nested, name, rest, value),
+        _ => Err(Error::custom(format!("field `{}` conflicts with a plain value", name))),
+    }
+}
+
+impl Node {
+    /// A description of the node for errors.
+    fn kind(&self) -> &'static str {
+        match self {
+            Node::Text(_) => "a text field",
+            Node::File(_) => "an uploaded file",
+            Node::Seq(_) => "a repeated field",
+            Node::Map(_) => "nested fields",
+        }
+    }
+}
+
+/// Deserializes a node of the form, named for errors.
+struct NodeDeserializer {
+    name: String,
+    node: Node,
+}
+
+impl NodeDeserializer {
+    fn unexpected(&self, expected: &str) -> Error {
+        let name = if self.name.is_empty() { "form" } else { &self.name };
+        Error::custom(format!(
+            "invalid value for `{}`: expected {}, found {}",
+            name,
+            expected,
+            self.node.kind()
+        ))
+    }
+
+    fn text(self, expected: &str) -> Result<String, Error> {
+        match self.node {
+            Node::Text(text) => Ok(text),
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(self, expected: &str) -> Result<T, Error> {
+        let name = self.name.clone();
+        let text = self.text(expected)?;
+        text.parse().map_err(|_| {
+            Error::custom(format!(
+                "invalid value `{}` for `{}`, expected {}",
+                text, name, expected
+            ))
+        })
+    }
+
+    /// The elements of the node as a sequence.
+    fn elements(self) -> Result<Elements, Error> {
+        let values = match self.node {
+            Node::Seq(values) => values,
+            Node::Map(entries) => {
+                // Indexed keys, such as `tag[0]`.
+                let mut indexed = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    match key.parse::<usize>() {
+                        Ok(idx) => indexed.push((idx, value)),
+                        Err(_) => {
+                            let name = format!("{}[{}]", self.name, key);
+                            let message = format!("expected an index, found `{}`", name);
+                            return Err(Error::custom(message));
+                        }
+                    }
+                }
+                indexed.sort_by_key(|(idx, _)| *idx);
+                indexed.into_iter().map(|(_, value)| value).collect()
+            }
+            single => vec![single],
+        };
+        Ok(Elements {
+            name: self.name,
+            values: values.into_iter(),
+        })
+    }
+
+    fn entries(self) -> Result<Entries, Error> {
+        match self.node {
+            Node::Map(entries) => Ok(Entries {
+                name: self.name,
+                entries: entries.into_iter(),
+                value: None,
+            }),
+            _ => Err(self.unexpected("nested fields")),
+        }
+    }
+}
+
+macro_rules! parse_text {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse::<$ty>(stringify!($ty))?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for NodeDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Text(text) => visitor.visit_string(text),
+            Node::Seq(_) => visitor.visit_seq(self.elements()?),
+            Node::Map(_) => visitor.visit_map(self.entries()?),
+            Node::File(_) => Err(self.unexpected("a plain value")),
+        }
+    }
+
+    parse_text! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_char => visit_char(char),
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.text("a string")?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.text("a string")?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_byte_buf(self.text("bytes")?.into_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_byte_buf(self.text("bytes")?.into_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name != UploadedFile::MARKER {
+            return visitor.visit_newtype_struct(self);
+        }
+        match self.node {
+            Node::File(file) => file.hand_to(visitor),
+            _ => Err(self.unexpected("an uploaded file")),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(self.elements()?)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(self.elements()?)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(self.elements()?)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(self.entries()?)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(self.entries()?)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: String = self.text("a variant")?;
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.text("an identifier")?)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Access to the values of a repeated or indexed key.
+struct Elements {
+    name: String,
+    values: std::vec::IntoIter<Node>,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.values.next() {
+            Some(node) => {
+                let name = self.name.clone();
+                seed.deserialize(NodeDeserializer { name, node }).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Access to the nested keys below a key, or to the top-level fields of the form.
+struct Entries {
+    name: String,
+    entries: std::vec::IntoIter<(String, Node)>,
+    /// The entry whose key was just deserialized.
+    value: Option<(String, Node)>,
+}
+
+impl<'de> de::MapAccess<'de> for Entries {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, node)) => {
+                let name = if self.name.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}[{}]", self.name, key)
+                };
+                let key = seed.deserialize(key.into_deserializer())?;
+                self.value = Some((name, node));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, node) = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("value requested before key"))?;
+        seed.deserialize(NodeDeserializer { name, node })
+    }
+}
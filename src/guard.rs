@@ -0,0 +1,175 @@
+//! Guards for selecting among endpoints by more than path and method.
+//!
+//! A guard is a predicate on requests, attached to an endpoint with `EndpointData::guard`. Several
+//! endpoints can be registered for the same path and method as long as the earlier ones have
+//! guards; a request is handled by the first endpoint whose guards all accept it.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #![feature(async_await)]
+//! use tide::guard;
+//!
+//! let mut app = tide::App::new(());
+//! app.at("/items")
+//!     .get(async || r#"["apple"]"#)
+//!     .guard(guard::accept("application/json"));
+//! app.at("/items")
+//!     .get(async || "apple\n")
+//!     .guard(guard::accept("text/csv"));
+//! ```
+//!
+//! Here, a request accepting neither `application/json` nor `text/csv` is answered with
+//! `406 Not Acceptable`.
+
+use crate::headers::{self, TypedHeader};
+use crate::Request;
+
+/// A predicate deciding whether an endpoint handles a request.
+///
+/// Guards are implemented for closures taking a `&Request` and returning a `bool`.
+pub trait Guard: Send + Sync + 'static {
+    /// Whether the endpoint accepts `req`.
+    fn check(&self, req: &Request) -> bool;
+
+    /// The status of the response if no endpoint accepts a request because of this guard.
+    ///
+    /// Defaults to `404 Not Found`.
+    fn rejection(&self) -> http::StatusCode {
+        http::StatusCode::NOT_FOUND
+    }
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&Request) -> bool + Send + Sync + 'static,
+{
+    fn check(&self, req: &Request) -> bool {
+        (self)(req)
+    }
+}
+
+/// The media type of a header value such as `text/html; charset=utf-8`, without parameters.
+fn essence(value: &str) -> &str {
+    value.split(';').next().unwrap_or("").trim()
+}
+
+/// A guard accepting requests whose `Accept` header allows the media type `mime`.
+///
+/// Requests without an `Accept` header are accepted. If no endpoint accepts a request, the
+/// response is `406 Not Acceptable`.
+pub fn accept(mime: &str) -> impl Guard {
+    Accept(mime.to_ascii_lowercase())
+}
+
+struct Accept(String);
+
+impl Guard for Accept {
+    fn check(&self, req: &Request) -> bool {
+        let accept = match req.headers().get(http::header::ACCEPT) {
+            Some(accept) => accept,
+            None => return true,
+        };
+        // The most specific range decides, so `application/json;q=0, */*` refuses JSON.
+        match headers::Accept::decode(&[accept]) {
+            Some(accept) => headers::media_quality(&accept.0, &self.0) > 0.0,
+            None => false,
+        }
+    }
+
+    fn rejection(&self) -> http::StatusCode {
+        http::StatusCode::NOT_ACCEPTABLE
+    }
+}
+
+/// A guard accepting requests whose `Content-Type` is the media type `mime`, ignoring parameters
+/// such as the charset.
+///
+/// If no endpoint accepts a request, the response is `415 Unsupported Media Type`.
+pub fn content_type(mime: &str) -> impl Guard {
+    ContentType(mime.to_ascii_lowercase())
+}
+
+struct ContentType(String);
+
+impl Guard for ContentType {
+    fn check(&self, req: &Request) -> bool {
+        req.headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| essence(value).eq_ignore_ascii_case(&self.0))
+    }
+
+    fn rejection(&self) -> http::StatusCode {
+        http::StatusCode::UNSUPPORTED_MEDIA_TYPE
+    }
+}
+
+/// A guard accepting requests with a header `name` equal to `value`, such as an API version.
+pub fn header(name: http::header::HeaderName, value: &str) -> impl Guard {
+    let value = value.to_owned();
+    move |req: &Request| req.headers().get(&name).map_or(false, |v| v == value.as_str())
+}
+
+/// A guard accepting requests whose query string contains the parameter `name`, with or without a
+/// value.
+pub fn query(name: &str) -> impl Guard {
+    let name = name.to_owned();
+    move |req: &Request| {
+        req.uri().query().map_or(false, |query| {
+            query
+                .split('&')
+                .any(|param| param.split('=').next() == Some(name.as_str()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(header: http::header::HeaderName, value: &str) -> Request {
+        http::Request::get("/?flag&x=1")
+            .header(header, value)
+            .body(http_service::Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn accept_ranges() {
+        let json = accept("application/json");
+        for value in &["application/json", "text/html, application/*", "*/*;q=0.1"] {
+            assert!(json.check(&request(http::header::ACCEPT, value)), "{}", value);
+        }
+        let refused = &[
+            "text/csv",
+            "text/*",
+            "application/json;q=0",
+            "*/*; q=0.0",
+            "application/json;q=0, */*",
+        ];
+        for value in refused {
+            assert!(!json.check(&request(http::header::ACCEPT, value)), "{}", value);
+        }
+        assert_eq!(json.rejection(), http::StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[test]
+    fn content_types() {
+        let json = content_type("application/json");
+        let req = request(http::header::CONTENT_TYPE, "Application/JSON; charset=utf-8");
+        assert!(json.check(&req));
+        let req = request(http::header::CONTENT_TYPE, "text/csv");
+        assert!(!json.check(&req));
+    }
+
+    #[test]
+    fn headers_and_queries() {
+        let req = request(http::header::HeaderName::from_static("api-version"), "2");
+        assert!(header(http::header::HeaderName::from_static("api-version"), "2").check(&req));
+        assert!(!header(http::header::HeaderName::from_static("api-version"), "1").check(&req));
+        assert!(query("flag").check(&req));
+        assert!(query("x").check(&req));
+        assert!(!query("y").check(&req));
+    }
+}
@@ -4,11 +4,18 @@
 //! automatically parse out information from a request.
 
 use futures::future;
+use percent_encoding::percent_decode;
 use std::borrow::Cow;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
-use crate::{configuration::Store, Extract, ExtractSeed, IntoResponse, Request, Response, RouteMatch};
+use crate::{
+    configuration::Store,
+    fields::{self, Node},
+    Extract, ExtractSeed, IntoResponse, Rejection, RejectionKind, Request, Response, RouteMatch,
+};
+
+pub use crate::params::Params;
 
 /// Header and metadata for a request.
 ///
@@ -53,6 +60,15 @@ impl Head {
     }
 }
 
+/// Fail to extract with a rejection by `extractor`.
+fn reject<T>(
+    kind: RejectionKind,
+    extractor: &'static str,
+    message: impl Into<String>,
+) -> future::Ready<Result<T, Response>> {
+    future::err(Rejection::new(kind, extractor, message).into_response())
+}
+
 pub struct NamedHeader(pub http::header::HeaderName);
 
 pub struct Header<T>(pub T);
@@ -74,7 +90,11 @@ impl<T: From<http::header::HeaderValue> + Send + 'static, S: 'static> ExtractSee
         let header = req.headers().get(&self.0);
         match header {
             Some(value) => future::ok(Header(value.clone().into())),
-            None => future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
+            None => reject(
+                RejectionKind::Missing,
+                "head::Header",
+                format!("Missing header `{}`", self.0),
+            ),
         }
     }
 }
@@ -95,12 +115,59 @@ impl<T: From<http::header::HeaderValue> + Send + 'static, S: 'static> ExtractSee
     }
 }
 
+/// Percent-decode a path `segment`, or the remainder of the path matched by a `*` wildcard.
+///
+/// Each `/`-separated piece is decoded on its own. Returns `None` if a piece is not valid UTF-8
+/// after decoding, or if it contains an encoded slash, which would be indistinguishable from a
+/// separator once decoded.
+pub(crate) fn decode_segment(segment: &str) -> Option<Cow<'_, str>> {
+    if !segment.contains('%') {
+        return Some(Cow::Borrowed(segment));
+    }
+
+    let mut decoded = String::with_capacity(segment.len());
+    for (i, piece) in segment.split('/').enumerate() {
+        let piece = percent_decode(piece.as_bytes()).decode_utf8().ok()?;
+        if piece.contains('/') {
+            return None;
+        }
+        if i > 0 {
+            decoded.push('/');
+        }
+        decoded.push_str(&piece);
+    }
+    Some(Cow::Owned(decoded))
+}
+
+/// Percent-decode a path `segment` and parse it as `T`.
+///
+/// Fails with a `BAD_REQUEST` response if the segment does not decode or does not parse.
+fn parse_segment<T: std::str::FromStr>(
+    segment: &str,
+    extractor: &'static str,
+) -> Result<T, Response> {
+    decode_segment(segment)
+        .and_then(|segment| segment.parse().ok())
+        .ok_or_else(|| {
+            let message = format!("Invalid path segment `{}`", segment);
+            Rejection::new(RejectionKind::Malformed, extractor, message).into_response()
+        })
+}
+
+/// The message for path extractors used without a route match, e.g. in a default handler.
+const NO_ROUTE_MATCH: &str = "Path segments are not available without a matched route";
 
 /// An extractor for path segments.
 ///
 /// Routes can use wildcard path segments (`{}`), which are then extracted by the endpoint using
 /// this `Path` extractor. Each `Path<T>` argument to an extractor parses the next wildcard segment
-/// as type `T`, failing with a `NOT_FOUND` response if the segment fails to parse.
+/// as type `T`, failing with a `BAD_REQUEST` response if the segment fails to parse.
+///
+/// The segment is percent-decoded before parsing, so `/files/my%20doc` yields `my doc`. For a
+/// `*` wildcard, each `/`-separated piece of the matched path is decoded on its own. Segments
+/// containing an encoded slash (`%2F`) are rejected with a `BAD_REQUEST` response, so that
+/// `a%2F..%2Fetc` cannot pass for `a/../etc`. Use [`RawPath`](struct.RawPath.html) to get the
+/// segment as it was sent.
 ///
 /// # Examples
 ///
@@ -141,6 +208,43 @@ impl<T> DerefMut for Path<T> {
 struct PathIdx(usize);
 
 impl<T: Send + 'static + std::str::FromStr, S: 'static> Extract<S> for Path<T> {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let &PathIdx(i) = req.extensions().get::<PathIdx>().unwrap_or(&PathIdx(0));
+        req.extensions_mut().insert(PathIdx(i + 1));
+        match params {
+            Some(params) => future::ready(parse_segment(params.vec[i], "head::Path").map(Path)),
+            None => reject(RejectionKind::Internal, "head::Path", NO_ROUTE_MATCH),
+        }
+    }
+}
+
+/// An extractor for path segments without percent-decoding.
+///
+/// This works like [`Path`](struct.Path.html), and takes its segment from the same sequence of
+/// wildcard segments, but parses the segment exactly as it appears in the request path, e.g.
+/// `my%20doc` for `/files/my%20doc`.
+pub struct RawPath<T>(pub T);
+
+impl<T> Deref for RawPath<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for RawPath<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Send + 'static + std::str::FromStr, S: 'static> Extract<S> for RawPath<T> {
     type Fut = future::Ready<Result<Self, Response>>;
     fn extract(
         data: &mut S,
@@ -152,10 +256,14 @@ impl<T: Send + 'static + std::str::FromStr, S: 'static> Extract<S> for Path<T> {
         req.extensions_mut().insert(PathIdx(i + 1));
         match params {
             Some(params) => match params.vec[i].parse() {
-                Ok(t) => future::ok(Path(t)),
-                Err(_) => future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
+                Ok(t) => future::ok(RawPath(t)),
+                Err(_) => reject(
+                    RejectionKind::Malformed,
+                    "head::RawPath",
+                    format!("Invalid path segment `{}`", params.vec[i]),
+                ),
             },
-            None => future::err(http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+            None => reject(RejectionKind::Internal, "head::RawPath", NO_ROUTE_MATCH),
         }
     }
 }
@@ -172,6 +280,8 @@ pub trait NamedSegment: Send + 'static + std::str::FromStr {
 /// FromStr trait. Fails with a `BAD_REQUEST` response if the segment is not found, fails to
 /// parse or if multiple identically named segments exist.
 ///
+/// Like with `Path`, the segment is percent-decoded before parsing.
+///
 /// # Examples
 ///
 /// Extracting a `Number` from a named path segment with:
@@ -232,22 +342,24 @@ impl<T: NamedSegment, S: 'static> Extract<S> for Named<T> {
         store: &Store,
     ) -> Self::Fut {
         match params {
-            Some(params) => params
-                .map
-                .get(T::NAME)
-                .and_then(|segment| segment.parse().ok())
-                .map_or(
-                    future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
-                    |t| future::ok(Named(t)),
-                ),
-            None => future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
+            Some(params) => match params.map.get(T::NAME) {
+                Some(segment) => future::ready(parse_segment(segment, "head::Named").map(Named)),
+                None => missing_segment(T::NAME),
+            },
+            None => missing_segment(T::NAME),
         }
     }
 }
 
+fn missing_segment<T>(name: &str) -> future::Ready<Result<T, Response>> {
+    let message = format!("Missing path segment `{}`", name);
+    reject(RejectionKind::Missing, "head::Named", message)
+}
+
 /// A seed extracting a particular segment.
 ///
-/// This extracts any `Named<T>` where `T: std::str::FromStr` by looking up the particular segment.
+/// This extracts any `Named<T>` where `T: std::str::FromStr` by looking up the particular segment,
+/// percent-decoded like with `Path`.
 pub struct SegmentName(pub Cow<'static, str>);
 
 impl<T: std::str::FromStr + Send + 'static, S: 'static> ExtractSeed<Named<T>, S> for SegmentName {
@@ -260,15 +372,62 @@ impl<T: std::str::FromStr + Send + 'static, S: 'static> ExtractSeed<Named<T>, S>
         store: &Store,
     ) -> Self::Fut {
         match params {
-            Some(params) => params
-                .map
-                .get(self.0.as_ref())
-                .and_then(|segment| segment.parse().ok())
-                .map_or(
-                    future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
-                    |t| future::ok(Named(t)),
-                ),
-            None => future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
+            Some(params) => match params.map.get(self.0.as_ref()) {
+                Some(segment) => future::ready(parse_segment(segment, "head::Named").map(Named)),
+                None => missing_segment(&self.0),
+            },
+            None => missing_segment(&self.0),
+        }
+    }
+}
+
+/// An extractor for the subdomain of a request to a wildcard host.
+///
+/// For a router set up with `App::host("*.example.com")`, this is the label matched by the `*`,
+/// e.g. `acme` for a request to `acme.example.com`. Fails with an `INTERNAL_SERVER_ERROR` response
+/// if the request was not routed through a wildcard host.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::head::Subdomain;
+///
+/// async fn tenant(Subdomain(tenant): Subdomain) -> String {
+///     format!("Welcome, {}!", tenant)
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.host("*.example.com").at("/").get(tenant);
+///     app.serve()
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subdomain(pub String);
+
+impl Deref for Subdomain {
+    type Target = String;
+    fn deref(&self) -> &String {
+        &self.0
+    }
+}
+
+impl<S: 'static> Extract<S> for Subdomain {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<Subdomain>() {
+            Some(subdomain) => future::ok(subdomain.clone()),
+            None => reject(
+                RejectionKind::Internal,
+                "head::Subdomain",
+                "The request was not routed through a wildcard host",
+            ),
         }
     }
 }
@@ -289,9 +448,116 @@ where
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        req.uri().query().and_then(|q| q.parse().ok()).map_or(
-            future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
-            |q| future::ok(UrlQuery(q)),
-        )
+        match req.uri().query() {
+            Some(query) => match query.parse() {
+                Ok(q) => future::ok(UrlQuery(q)),
+                Err(_) => reject(
+                    RejectionKind::Malformed,
+                    "head::UrlQuery",
+                    "Invalid query string",
+                ),
+            },
+            None => reject(RejectionKind::Missing, "head::UrlQuery", "Missing query string"),
+        }
     }
 }
+
+/// An extractor deserializing the query string into a `T`.
+///
+/// Besides plain `key=value` pairs, the query string may contain nested keys (`filter[name]=tide`)
+/// and sequences, either indexed (`tag[0]=a`) or given as repeated keys (`tag=a&tag=b`, or
+/// `tag[]=a&tag[]=b`). A single `tag=a` deserializes into a sequence as well. Fields that are
+/// missing from the query can be made optional with `Option` or `#[serde(default)]`. A request
+/// without a query string is treated like an empty one.
+///
+/// Fails with a `BAD_REQUEST` response describing the problem if deserialization fails.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// #[macro_use]
+/// extern crate serde_derive;
+///
+/// use tide::head::Query;
+///
+/// #[derive(Deserialize)]
+/// struct Page {
+///     page: Option<u32>,
+///     sort: Option<String>,
+/// }
+///
+/// async fn list(Query(page): Query<Page>) -> String {
+///     format!("page {} sorted by {:?}", page.page.unwrap_or(1), page.sort)
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/items").get(list);
+///     app.serve()
+/// }
+/// ```
+pub struct Query<T>(pub T);
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Query<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<S, T> Extract<S> for Query<T>
+where
+    T: Send + serde::de::DeserializeOwned + 'static,
+    S: 'static,
+{
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let fields = match query_fields(req.uri().query().unwrap_or("")) {
+            Some(fields) => fields,
+            None => {
+                let message = "Invalid query string: not valid UTF-8";
+                return reject(RejectionKind::Malformed, "head::Query", message);
+            }
+        };
+        match fields::deserialize(fields) {
+            Ok(t) => future::ok(Query(t)),
+            Err(err) => reject(
+                RejectionKind::Malformed,
+                "head::Query",
+                format!("Invalid query string: {}", err),
+            ),
+        }
+    }
+}
+
+/// Split a query string into its percent-decoded fields, or `None` if one is not valid UTF-8.
+fn query_fields(query: &str) -> Option<Vec<(String, Node)>> {
+    fn decode(part: &str) -> Option<String> {
+        let part = part.replace('+', " ");
+        let decoded = percent_decode(part.as_bytes()).decode_utf8().ok()?;
+        Some(decoded.into_owned())
+    }
+
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let name = decode(parts.next().unwrap_or(""))?;
+            let value = decode(parts.next().unwrap_or(""))?;
+            Some((name, Node::Text(value)))
+        })
+        .collect()
+}
@@ -0,0 +1,706 @@
+//! Typed representations of standard HTTP headers.
+//!
+//! Each header implements `TypedHeader`, which parses it from the values of a request and encodes
+//! it into a header value. The headers in this module can be used directly as extractors, failing
+//! with a `BAD_REQUEST` response if the header is missing or malformed, and can be attached to any
+//! response with `IntoResponse::with_header`.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #![feature(async_await, futures_api)]
+//! use std::time::SystemTime;
+//! use tide::headers::{ETag, IfNoneMatch, LastModified};
+//! use tide::IntoResponse;
+//!
+//! async fn article(if_none_match: IfNoneMatch) -> tide::Response {
+//!     let etag = ETag::strong("v2");
+//!     if if_none_match.matches(&etag) {
+//!         return http::StatusCode::NOT_MODIFIED.into_response();
+//!     }
+//!     "article"
+//!         .with_header(etag)
+//!         .with_header(LastModified(SystemTime::now()))
+//!         .into_response()
+//! }
+//! ```
+
+use futures::future;
+use http::header::{self, HeaderName, HeaderValue, InvalidHeaderValue};
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::{
+    configuration::Store, Extract, IntoResponse, Rejection, RejectionKind, Request, Response,
+    RouteMatch,
+};
+
+/// A header with a typed representation.
+pub trait TypedHeader: Sized + Send + 'static {
+    /// The name of the header.
+    fn name() -> HeaderName;
+
+    /// Parse the header from all of its values in a request, in order.
+    ///
+    /// Returns `None` if the values are malformed. `values` is never empty.
+    fn decode(values: &[&HeaderValue]) -> Option<Self>;
+
+    /// Encode the header as a single value.
+    ///
+    /// Fails if the header contains characters that are not allowed in header values, such as
+    /// line breaks.
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue>;
+
+    /// Parse the header from `headers`, returning `None` if it is missing or malformed.
+    fn from_headers(headers: &header::HeaderMap) -> Option<Self> {
+        let values: Vec<_> = headers.get_all(Self::name()).iter().collect();
+        if values.is_empty() {
+            None
+        } else {
+            Self::decode(&values)
+        }
+    }
+}
+
+/// The value of a header that may only appear once.
+fn single<'a>(values: &[&'a HeaderValue]) -> Option<&'a str> {
+    match values {
+        [value] => value.to_str().ok().map(str::trim),
+        _ => None,
+    }
+}
+
+/// The elements of a comma-separated list header, which may be split over several values.
+fn list<'a>(values: &[&'a HeaderValue]) -> Option<Vec<&'a str>> {
+    let mut items = Vec::new();
+    for value in values {
+        items.extend(
+            value
+                .to_str()
+                .ok()?
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty()),
+        );
+    }
+    Some(items)
+}
+
+/// Extract `H` from the request, failing with a `BAD_REQUEST` response.
+fn extract_header<H: TypedHeader>(req: &Request) -> Result<H, Response> {
+    let values: Vec<_> = req.headers().get_all(H::name()).iter().collect();
+    let (kind, message) = if values.is_empty() {
+        (RejectionKind::Missing, "Missing")
+    } else {
+        match H::decode(&values) {
+            Some(header) => return Ok(header),
+            None => (RejectionKind::Malformed, "Invalid"),
+        }
+    };
+    let message = format!("{} header `{}`", message, H::name());
+    Err(Rejection::new(kind, "headers::TypedHeader", message).into_response())
+}
+
+macro_rules! extract_typed_header {
+    ($($header:ty),*) => {
+        $(
+            impl<S: 'static> Extract<S> for $header {
+                type Fut = future::Ready<Result<Self, Response>>;
+                fn extract(
+                    data: &mut S,
+                    req: &mut Request,
+                    params: &Option<RouteMatch<'_>>,
+                    store: &Store,
+                ) -> Self::Fut {
+                    future::ready(extract_header(req))
+                }
+            }
+        )*
+    };
+}
+
+extract_typed_header!(
+    Accept,
+    Authorization,
+    ContentLength,
+    ContentType,
+    ETag,
+    IfModifiedSince,
+    IfNoneMatch,
+    LastModified,
+    Range,
+    UserAgent
+);
+
+/// The media type of a request or response body, such as `text/html; charset=utf-8`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentType(pub String);
+
+impl ContentType {
+    /// `application/json`.
+    pub fn json() -> ContentType {
+        ContentType("application/json".to_owned())
+    }
+
+    /// `text/html; charset=utf-8`.
+    pub fn html() -> ContentType {
+        ContentType("text/html; charset=utf-8".to_owned())
+    }
+
+    /// `text/plain; charset=utf-8`.
+    pub fn text() -> ContentType {
+        ContentType("text/plain; charset=utf-8".to_owned())
+    }
+
+    /// The media type without parameters, in lowercase, such as `text/html`.
+    pub fn essence(&self) -> String {
+        self.0
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase()
+    }
+
+    /// The value of the parameter `name`, such as the `charset`, without quotes.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.0.split(';').skip(1).find_map(|param| {
+            let mut parts = param.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let value = parts.next()?.trim();
+            if key.eq_ignore_ascii_case(name) {
+                Some(value.trim_matches('"'))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl TypedHeader for ContentType {
+    fn name() -> HeaderName {
+        header::CONTENT_TYPE
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        single(values)
+            .filter(|value| value.contains('/'))
+            .map(|value| ContentType(value.to_owned()))
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(&self.0)
+    }
+}
+
+/// The length of a body in bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContentLength(pub u64);
+
+impl TypedHeader for ContentLength {
+    fn name() -> HeaderName {
+        header::CONTENT_LENGTH
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        single(values)?.parse().ok().map(ContentLength)
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        Ok(HeaderValue::from(self.0))
+    }
+}
+
+/// A value of a list header with its quality, e.g. `text/html;q=0.8`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualityItem {
+    /// The value without the quality parameter.
+    pub value: String,
+    /// The quality between `0.0` and `1.0`, defaulting to `1.0`.
+    pub quality: f32,
+}
+
+impl QualityItem {
+    fn parse(item: &str) -> Option<QualityItem> {
+        let mut quality = 1.0;
+        let mut value = Vec::new();
+        for (idx, part) in item.split(';').map(str::trim).enumerate() {
+            if idx > 0 && (part.starts_with("q=") || part.starts_with("Q=")) {
+                quality = part[2..].parse::<f32>().ok().filter(|q| *q >= 0.0 && *q <= 1.0)?;
+            } else {
+                value.push(part);
+            }
+        }
+        Some(QualityItem {
+            value: value.join(";"),
+            quality,
+        })
+    }
+}
+
+/// The quality of the media type `mime` under the most specific of the media ranges `items`, or
+/// `0.0` if no range matches.
+///
+/// An exact match is more specific than `type/*`, which is more specific than `*/*`; among equally
+/// specific ranges the first one decides.
+pub(crate) fn media_quality(items: &[QualityItem], mime: &str) -> f32 {
+    let mime = mime.to_ascii_lowercase();
+    let kind = mime.split('/').next().unwrap_or("");
+    let mut best = None;
+    for item in items {
+        let range = item.value.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        let specificity = if range == mime {
+            2
+        } else if range.ends_with("/*") && range[..range.len() - 2] == *kind {
+            1
+        } else if range == "*/*" {
+            0
+        } else {
+            continue;
+        };
+        if best.map_or(true, |(s, _)| specificity > s) {
+            best = Some((specificity, item.quality));
+        }
+    }
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+/// The media types acceptable for the response, ordered by decreasing quality.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accept(pub Vec<QualityItem>);
+
+impl Accept {
+    /// The quality of the media type `mime` under the most specific matching range, or `0.0` if
+    /// no range matches.
+    pub fn quality(&self, mime: &str) -> f32 {
+        media_quality(&self.0, mime)
+    }
+
+    /// The acceptable media type among `available` with the highest quality, preferring earlier
+    /// entries on ties.
+    pub fn negotiate<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&'a str, f32)> = None;
+        for mime in available {
+            let quality = self.quality(mime);
+            if quality > 0.0 && best.map_or(true, |(_, q)| quality > q) {
+                best = Some((*mime, quality));
+            }
+        }
+        best.map(|(mime, _)| mime)
+    }
+}
+
+impl TypedHeader for Accept {
+    fn name() -> HeaderName {
+        header::ACCEPT
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        let mut items = list(values)?
+            .into_iter()
+            .map(QualityItem::parse)
+            .collect::<Option<Vec<_>>>()?;
+        // A stable sort keeps the order of the client among items of equal quality.
+        items.sort_by(|a, b| b.quality.partial_cmp(&a.quality).unwrap());
+        Some(Accept(items))
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        let items: Vec<_> = self
+            .0
+            .iter()
+            .map(|item| {
+                if item.quality < 1.0 {
+                    format!("{};q={}", item.value, item.quality)
+                } else {
+                    item.value.clone()
+                }
+            })
+            .collect();
+        HeaderValue::from_str(&items.join(", "))
+    }
+}
+
+/// Credentials for authenticating the request, such as `Bearer <token>`.
+///
+/// The `Debug` output leaves out the credentials, so that they do not end up in logs.
+#[derive(Clone, PartialEq)]
+pub struct Authorization {
+    /// The authentication scheme, such as `Basic` or `Bearer`.
+    pub scheme: String,
+    /// The credentials following the scheme.
+    pub credentials: String,
+}
+
+impl Authorization {
+    /// Authorization with a bearer `token`.
+    pub fn bearer(token: &str) -> Authorization {
+        Authorization {
+            scheme: "Bearer".to_owned(),
+            credentials: token.to_owned(),
+        }
+    }
+
+    /// The token, if the scheme is `Bearer`.
+    pub fn bearer_token(&self) -> Option<&str> {
+        if self.scheme.eq_ignore_ascii_case("bearer") {
+            Some(&self.credentials)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Authorization")
+            .field("scheme", &self.scheme)
+            .field("credentials", &format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl TypedHeader for Authorization {
+    fn name() -> HeaderName {
+        header::AUTHORIZATION
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        let mut parts = single(values)?.splitn(2, ' ');
+        let scheme = parts.next().filter(|scheme| !scheme.is_empty())?;
+        Some(Authorization {
+            scheme: scheme.to_owned(),
+            credentials: parts.next().unwrap_or("").trim().to_owned(),
+        })
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(&format!("{} {}", self.scheme, self.credentials))
+    }
+}
+
+/// An entity tag identifying a version of a resource.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ETag {
+    /// The tag without quotes.
+    pub tag: String,
+    /// Whether the tag is weak (`W/"..."`), i.e. only identifies semantically equal versions.
+    pub weak: bool,
+}
+
+impl ETag {
+    /// A strong entity tag.
+    pub fn strong(tag: &str) -> ETag {
+        ETag {
+            tag: tag.to_owned(),
+            weak: false,
+        }
+    }
+
+    /// A weak entity tag.
+    pub fn weak(tag: &str) -> ETag {
+        ETag {
+            tag: tag.to_owned(),
+            weak: true,
+        }
+    }
+
+    fn to_header_string(&self) -> String {
+        let prefix = if self.weak { "W/" } else { "" };
+        format!("{}\"{}\"", prefix, self.tag)
+    }
+
+    fn parse(value: &str) -> Option<ETag> {
+        let (weak, tag) = if value.starts_with("W/") {
+            (true, &value[2..])
+        } else {
+            (false, value)
+        };
+        if tag.len() < 2 || !tag.starts_with('"') || !tag.ends_with('"') {
+            return None;
+        }
+        Some(ETag {
+            tag: tag[1..tag.len() - 1].to_owned(),
+            weak,
+        })
+    }
+}
+
+impl TypedHeader for ETag {
+    fn name() -> HeaderName {
+        header::ETAG
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        ETag::parse(single(values)?)
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(&self.to_header_string())
+    }
+}
+
+/// The entity tags of cached versions, for conditional requests.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IfNoneMatch {
+    /// `*`, matching any version.
+    Any,
+    /// A list of entity tags.
+    Tags(Vec<ETag>),
+}
+
+impl IfNoneMatch {
+    /// Whether `etag` matches, using weak comparison. A match means that the cached version is
+    /// current, and a `GET` request can be answered with `304 Not Modified`.
+    pub fn matches(&self, etag: &ETag) -> bool {
+        match self {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Tags(tags) => tags.iter().any(|tag| tag.tag == etag.tag),
+        }
+    }
+}
+
+impl TypedHeader for IfNoneMatch {
+    fn name() -> HeaderName {
+        header::IF_NONE_MATCH
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        let items = list(values)?;
+        if items == ["*"] {
+            return Some(IfNoneMatch::Any);
+        }
+        items
+            .into_iter()
+            .map(ETag::parse)
+            .collect::<Option<_>>()
+            .map(IfNoneMatch::Tags)
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        match self {
+            IfNoneMatch::Any => Ok(HeaderValue::from_static("*")),
+            IfNoneMatch::Tags(tags) => {
+                let tags: Vec<_> = tags.iter().map(ETag::to_header_string).collect();
+                HeaderValue::from_str(&tags.join(", "))
+            }
+        }
+    }
+}
+
+macro_rules! date_header {
+    ($(#[$doc:meta] $header:ident => $name:expr,)*) => {
+        $(
+            #[$doc]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            pub struct $header(pub SystemTime);
+
+            impl TypedHeader for $header {
+                fn name() -> HeaderName {
+                    $name
+                }
+
+                fn decode(values: &[&HeaderValue]) -> Option<Self> {
+                    httpdate::parse_http_date(single(values)?).ok().map($header)
+                }
+
+                fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+                    HeaderValue::from_str(&httpdate::fmt_http_date(self.0))
+                }
+            }
+        )*
+    };
+}
+
+date_header! {
+    /// The time of the cached version, for conditional requests.
+    IfModifiedSince => header::IF_MODIFIED_SINCE,
+    /// The time the resource was last modified.
+    LastModified => header::LAST_MODIFIED,
+}
+
+/// A range of bytes requested by a `Range` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ByteRange {
+    /// The bytes from the first to the last offset, inclusive, e.g. `0-499`.
+    FromTo(u64, u64),
+    /// The bytes from an offset to the end, e.g. `500-`.
+    From(u64),
+    /// The given number of bytes at the end, e.g. `-500`.
+    Last(u64),
+}
+
+impl ByteRange {
+    /// The first and last offset of the range within a body of `len` bytes, or `None` if the
+    /// range is not satisfiable.
+    pub fn bounds(&self, len: u64) -> Option<(u64, u64)> {
+        let (first, last) = match *self {
+            ByteRange::FromTo(first, last) => (first, last.min(len.checked_sub(1)?)),
+            ByteRange::From(first) => (first, len.checked_sub(1)?),
+            ByteRange::Last(0) => return None,
+            ByteRange::Last(n) => (len.saturating_sub(n), len.checked_sub(1)?),
+        };
+        if first <= last {
+            Some((first, last))
+        } else {
+            None
+        }
+    }
+}
+
+/// The byte ranges of the body requested by the client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range(pub Vec<ByteRange>);
+
+impl TypedHeader for Range {
+    fn name() -> HeaderName {
+        header::RANGE
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        let value = single(values)?;
+        if !value.starts_with("bytes=") {
+            return None;
+        }
+        let ranges = value[6..]
+            .split(',')
+            .map(|range| {
+                let mut parts = range.trim().splitn(2, '-');
+                let first = parts.next()?;
+                let last = parts.next()?;
+                match (first.is_empty(), last.is_empty()) {
+                    (false, false) => {
+                        let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+                        if first <= last {
+                            Some(ByteRange::FromTo(first, last))
+                        } else {
+                            None
+                        }
+                    }
+                    (false, true) => first.parse().ok().map(ByteRange::From),
+                    (true, false) => last.parse().ok().map(ByteRange::Last),
+                    (true, true) => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Range(ranges))
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        let ranges: Vec<_> = self
+            .0
+            .iter()
+            .map(|range| match range {
+                ByteRange::FromTo(first, last) => format!("{}-{}", first, last),
+                ByteRange::From(first) => format!("{}-", first),
+                ByteRange::Last(n) => format!("-{}", n),
+            })
+            .collect();
+        HeaderValue::from_str(&format!("bytes={}", ranges.join(",")))
+    }
+}
+
+/// The client software making the request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserAgent(pub String);
+
+impl TypedHeader for UserAgent {
+    fn name() -> HeaderName {
+        header::USER_AGENT
+    }
+
+    fn decode(values: &[&HeaderValue]) -> Option<Self> {
+        single(values).map(|value| UserAgent(value.to_owned()))
+    }
+
+    fn encode(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn decode<H: TypedHeader>(values: &[&'static str]) -> Option<H> {
+        let values: Vec<_> = values.iter().map(|v| HeaderValue::from_static(*v)).collect();
+        let values: Vec<_> = values.iter().collect();
+        H::decode(&values)
+    }
+
+    #[test]
+    fn content_type() {
+        let ct: ContentType = decode(&["Text/HTML; charset=\"utf-8\""]).unwrap();
+        assert_eq!(ct.essence(), "text/html");
+        assert_eq!(ct.param("charset"), Some("utf-8"));
+        assert!(decode::<ContentType>(&["json"]).is_none());
+    }
+
+    #[test]
+    fn accept_negotiation() {
+        let accept: Accept = decode(&["text/*;q=0.5, application/json", "*/*;q=0.1"]).unwrap();
+        assert_eq!(accept.0[0].value, "application/json");
+        assert_eq!(accept.quality("text/csv"), 0.5);
+        assert_eq!(accept.quality("image/png"), 0.1);
+        assert_eq!(
+            accept.negotiate(&["text/html", "application/json"]),
+            Some("application/json")
+        );
+        assert!(decode::<Accept>(&["text/html;q=2"]).is_none());
+    }
+
+    #[test]
+    fn etags() {
+        let none_match: IfNoneMatch = decode(&["W/\"a\", \"b\""]).unwrap();
+        assert!(none_match.matches(&ETag::strong("a")));
+        assert!(!none_match.matches(&ETag::strong("c")));
+        assert_eq!(none_match.encode().unwrap(), "W/\"a\", \"b\"");
+        assert_eq!(decode::<IfNoneMatch>(&["*"]), Some(IfNoneMatch::Any));
+        assert!(decode::<IfNoneMatch>(&["a"]).is_none());
+    }
+
+    #[test]
+    fn dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        let date: IfModifiedSince = decode(&["Sun, 06 Nov 1994 08:49:37 GMT"]).unwrap();
+        assert_eq!(date.0, time);
+        assert_eq!(LastModified(time).encode().unwrap(), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn ranges() {
+        let range: Range = decode(&["bytes=0-499, 500-, -100"]).unwrap();
+        assert_eq!(
+            range.0,
+            vec![
+                ByteRange::FromTo(0, 499),
+                ByteRange::From(500),
+                ByteRange::Last(100)
+            ]
+        );
+        assert_eq!(range.0[0].bounds(200), Some((0, 199)));
+        assert_eq!(range.0[1].bounds(200), None);
+        assert_eq!(range.0[2].bounds(50), Some((0, 49)));
+        assert!(decode::<Range>(&["bytes=5-1"]).is_none());
+        assert!(decode::<Range>(&["items=0-1"]).is_none());
+    }
+
+    #[test]
+    fn authorization() {
+        let auth: Authorization = decode(&["Bearer abc.def"]).unwrap();
+        assert_eq!(auth.bearer_token(), Some("abc.def"));
+        assert_eq!(Authorization::bearer("xyz").encode().unwrap(), "Bearer xyz");
+        assert_eq!(
+            format!("{:?}", auth),
+            "Authorization { scheme: \"Bearer\", credentials: <redacted> }"
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(ContentType("text/html\r\nSet-Cookie: a=b".to_owned()).encode().is_err());
+        assert!(UserAgent("agent\0".to_owned()).encode().is_err());
+        assert!(Authorization::bearer("a\nb").encode().is_err());
+    }
+}
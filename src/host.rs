@@ -0,0 +1,119 @@
+use crate::{head::Subdomain, Request, Router};
+
+/// A pattern for the `Host` of requests handled by a router.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum HostPattern {
+    /// Matches exactly the given host name.
+    Exact(String),
+    /// Matches a single subdomain label in front of the given domain (`*.example.com`).
+    Wildcard(String),
+}
+
+impl HostPattern {
+    pub(crate) fn parse(pattern: &str) -> HostPattern {
+        let pattern = pattern.to_ascii_lowercase();
+        if pattern.starts_with("*.") {
+            HostPattern::Wildcard(pattern[2..].to_owned())
+        } else {
+            HostPattern::Exact(pattern)
+        }
+    }
+
+    /// Match `host`, returning the subdomain for wildcard patterns.
+    fn matches(&self, host: &str) -> Option<Option<Subdomain>> {
+        match self {
+            HostPattern::Exact(name) if name == host => Some(None),
+            HostPattern::Exact(_) => None,
+            HostPattern::Wildcard(domain) => {
+                if host.len() <= domain.len() + 1 || !host.ends_with(domain.as_str()) {
+                    return None;
+                }
+                let label = &host[..host.len() - domain.len()];
+                if !label.ends_with('.') {
+                    return None;
+                }
+                let label = &label[..label.len() - 1];
+                if label.contains('.') {
+                    return None;
+                }
+                Some(Some(Subdomain(label.to_owned())))
+            }
+        }
+    }
+}
+
+/// The host name of a request, lowercased and without the port.
+pub(crate) fn request_host(req: &Request) -> Option<String> {
+    let host = match req.headers().get(http::header::HOST) {
+        Some(host) => host.to_str().ok()?,
+        None => req.uri().host()?,
+    };
+    let host = match host.rfind(':') {
+        // Do not mistake the colons of an IPv6 literal such as `[::1]` for a port separator.
+        Some(idx) if !host[idx..].contains(']') => &host[..idx],
+        _ => host,
+    };
+    Some(host.to_ascii_lowercase())
+}
+
+/// Select the router for `host` among the `hosts` routers, falling back to `default`.
+///
+/// Exact patterns are preferred over wildcard patterns.
+pub(crate) fn select_router<'a, Data>(
+    default: &'a Router<Data>,
+    hosts: &'a [(HostPattern, Router<Data>)],
+    host: Option<&str>,
+) -> (&'a Router<Data>, Option<Subdomain>) {
+    let host = match host {
+        Some(host) => host,
+        None => return (default, None),
+    };
+
+    let exact = hosts.iter().find(|(pattern, _)| match pattern {
+        HostPattern::Exact(_) => pattern.matches(host).is_some(),
+        HostPattern::Wildcard(_) => false,
+    });
+    if let Some((_, router)) = exact {
+        return (router, None);
+    }
+
+    hosts
+        .iter()
+        .filter_map(|(pattern, router)| pattern.matches(host).map(|subdomain| (router, subdomain)))
+        .next()
+        .unwrap_or((default, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        let exact = HostPattern::parse("Example.com");
+        assert_eq!(exact.matches("example.com"), Some(None));
+        assert_eq!(exact.matches("api.example.com"), None);
+
+        let wildcard = HostPattern::parse("*.example.com");
+        let subdomain = wildcard.matches("api.example.com").unwrap().unwrap();
+        assert_eq!(subdomain.0, "api");
+        assert_eq!(wildcard.matches("example.com"), None);
+        assert_eq!(wildcard.matches("a.b.example.com"), None);
+        assert_eq!(wildcard.matches("apiexample.com"), None);
+    }
+
+    #[test]
+    fn host_without_port() {
+        let req = http::Request::get("/")
+            .header("Host", "API.example.com:8080")
+            .body(http_service::Body::empty())
+            .unwrap();
+        assert_eq!(request_host(&req), Some("api.example.com".to_owned()));
+
+        let req = http::Request::get("/")
+            .header("Host", "[::1]")
+            .body(http_service::Body::empty())
+            .unwrap();
+        assert_eq!(request_host(&req), Some("[::1]".to_owned()));
+    }
+}
@@ -17,23 +17,34 @@ pub mod configuration;
 mod cookies;
 mod endpoint;
 mod extract;
+mod fields;
+pub mod guard;
 pub mod head;
+pub mod headers;
+mod host;
 pub mod middleware;
+mod mount;
+mod params;
+mod rejection;
 mod request;
 mod response;
 mod router;
 #[cfg(feature = "hyper")]
 mod serve;
+mod urls;
 
 pub use crate::{
-    app::{App, AppData, Server},
+    app::{App, AppData, RouterHandle, Server},
     configuration::ExtractConfiguration,
     cookies::Cookies,
     endpoint::{Endpoint, Seeded},
     extract::{Extract, ExtractSeed},
     middleware::Middleware,
+    mount::OriginalUri,
+    rejection::{Rejection, RejectionKind, RenderRejection},
     request::{Compute, Computed, Request},
     response::{IntoResponse, Response},
-    router::{Resource, Router},
+    router::{AllowedMethods, Resource, RouteConflict, RouteInfo, Router, TrailingSlash},
+    urls::{UrlError, Urls},
 };
 pub use path_table::RouteMatch;
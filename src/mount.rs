@@ -0,0 +1,109 @@
+use futures::future::{self, FutureObj};
+use futures::prelude::*;
+use http_service::HttpService;
+use std::sync::Arc;
+
+use crate::{
+    configuration::Store, Endpoint, Extract, IntoResponse, Rejection, RejectionKind, Request,
+    Response, RouteMatch,
+};
+
+/// The URI of a request before it was handed to a mounted service.
+///
+/// A service mounted with `Resource::mount` sees request paths relative to its mount point. The
+/// original URI is kept in the request `extensions` as `OriginalUri`, and endpoints of a mounted
+/// tide app can use it as an extractor.
+#[derive(Clone, Debug)]
+pub struct OriginalUri(pub http::Uri);
+
+impl<S: 'static> Extract<S> for OriginalUri {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<OriginalUri>() {
+            Some(uri) => future::ok(uri.clone()),
+            None => future::err(
+                Rejection::new(
+                    RejectionKind::Internal,
+                    "OriginalUri",
+                    "The request was not forwarded to a mounted service",
+                )
+                .into_response(),
+            ),
+        }
+    }
+}
+
+/// An endpoint forwarding requests to an `HttpService`.
+pub(crate) struct Mount<S> {
+    service: Arc<S>,
+}
+
+impl<S> Mount<S> {
+    pub(crate) fn new(service: S) -> Mount<S> {
+        Mount {
+            service: Arc::new(service),
+        }
+    }
+}
+
+/// Rewrite the URI of `req` to the part matched by the trailing `*` wildcard of the route.
+fn strip_prefix(req: &mut Request, params: &Option<RouteMatch<'_>>) -> Result<(), Response> {
+    let original = req.uri().clone();
+    let rest = params
+        .as_ref()
+        .and_then(|params| params.vec.last().cloned())
+        .unwrap_or("");
+
+    let mut path_and_query = format!("/{}", rest.trim_start_matches('/'));
+    if let Some(query) = original.query() {
+        path_and_query.push('?');
+        path_and_query.push_str(query);
+    }
+
+    let malformed = || {
+        let message = format!("Invalid path `{}` for the mounted service", path_and_query);
+        Rejection::new(RejectionKind::Malformed, "Resource::mount", message).into_response()
+    };
+    let mut parts = original.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().map_err(|_| malformed())?);
+    *req.uri_mut() = http::Uri::from_parts(parts).map_err(|_| malformed())?;
+    req.extensions_mut().insert(OriginalUri(original));
+    Ok(())
+}
+
+impl<Data, S: HttpService> Endpoint<Data, ()> for Mount<S> {
+    type Fut = FutureObj<'static, Response>;
+
+    fn call(
+        &self,
+        data: Data,
+        mut req: Request,
+        params: Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        if let Err(resp) = strip_prefix(&mut req, &params) {
+            return FutureObj::new(Box::new(future::ready(resp)));
+        }
+
+        let service = self.service.clone();
+        FutureObj::new(Box::new(
+            async move {
+                let mut connection = match await!(service.connect().into_future()) {
+                    Ok(connection) => connection,
+                    Err(_) => {
+                        return http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                    }
+                };
+                match await!(service.respond(&mut connection, req).into_future()) {
+                    Ok(resp) => resp,
+                    Err(_) => http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                }
+            },
+        ))
+    }
+}
@@ -0,0 +1,402 @@
+//! Deserialization of path parameters with serde.
+
+use futures::future;
+use serde::de::{self, IntoDeserializer, Visitor};
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    configuration::Store, head::decode_segment, Extract, IntoResponse, Rejection, RejectionKind,
+    Request, Response, RouteMatch,
+};
+
+/// An extractor deserializing all wildcard segments of the path into a `T`.
+///
+/// A struct is deserialized from the named segments, so that `/orders/{customer}/{id}` can be
+/// extracted as a struct with the fields `customer` and `id`. A tuple or sequence is
+/// deserialized from all segments in order, named or not. If the route has a single segment, it
+/// can also be deserialized directly, e.g. as `Params<u64>`. Segments are percent-decoded like with
+/// `head::Path`.
+///
+/// Fails with a `BAD_REQUEST` response naming the offending segment if deserialization fails.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// #[macro_use]
+/// extern crate serde_derive;
+///
+/// use tide::head::Params;
+///
+/// #[derive(Deserialize)]
+/// struct OrderPath {
+///     customer: String,
+///     id: u64,
+/// }
+///
+/// async fn order(Params(path): Params<OrderPath>) -> String {
+///     format!("order {} of {}", path.id, path.customer)
+/// }
+///
+/// async fn item(Params((order, item)): Params<(u64, String)>) -> String {
+///     format!("item {} of order {}", item, order)
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/customers/{customer}/orders/{id:u64}").get(order);
+///     app.at("/orders/{:u64}/items/{}").get(item);
+///     app.serve()
+/// }
+/// ```
+pub struct Params<T>(pub T);
+
+impl<T> Deref for Params<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Params<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<S, T> Extract<S> for Params<T>
+where
+    T: Send + de::DeserializeOwned + 'static,
+    S: 'static,
+{
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let params = match params {
+            Some(params) => params,
+            None => {
+                let message = "Path segments are not available without a matched route";
+                return future::err(
+                    Rejection::new(RejectionKind::Internal, "head::Params", message)
+                        .into_response(),
+                );
+            }
+        };
+        match T::deserialize(ParamsDeserializer { params }) {
+            Ok(t) => future::ok(Params(t)),
+            Err(err) => {
+                let message = format!("Invalid path parameters: {}", err);
+                future::err(
+                    Rejection::new(RejectionKind::Malformed, "head::Params", message)
+                        .into_response(),
+                )
+            }
+        }
+    }
+}
+
+/// An error deserializing path parameters.
+#[derive(Debug)]
+pub(crate) struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Deserializes all segments of a route match.
+struct ParamsDeserializer<'a, 'b> {
+    params: &'b RouteMatch<'a>,
+}
+
+impl<'a, 'b> ParamsDeserializer<'a, 'b> {
+    /// The only segment, for deserializing a single value.
+    fn single(&self) -> Result<SegmentDeserializer<'b>, Error> {
+        match &*self.params.vec {
+            [value] => SegmentDeserializer::new("0".into(), value),
+            segments => Err(Error(format!(
+                "expected a single path segment, found {}",
+                segments.len()
+            ))),
+        }
+    }
+
+    fn segments(&self) -> Segments<'b, impl Iterator<Item = (Cow<'b, str>, &'b str)>> {
+        let segments = self.params.vec.iter().enumerate();
+        Segments {
+            segments: segments.map(|(idx, value)| (idx.to_string().into(), *value)),
+            value: None,
+        }
+    }
+
+    fn named_segments(&self) -> Segments<'b, impl Iterator<Item = (Cow<'b, str>, &'b str)>> {
+        let segments = self.params.map.iter();
+        Segments {
+            segments: segments.map(|(name, value)| (Cow::Borrowed(*name), *value)),
+            value: None,
+        }
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a, 'b> de::Deserializer<'de> for ParamsDeserializer<'a, 'b> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.params.map.is_empty() {
+            visitor.visit_seq(self.segments())
+        } else {
+            visitor.visit_map(self.named_segments())
+        }
+    }
+
+    forward_to_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_option
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(self.segments())
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        if self.params.vec.len() != len {
+            return Err(Error(format!(
+                "expected {} path segments, found {}",
+                len,
+                self.params.vec.len()
+            )));
+        }
+        visitor.visit_seq(self.segments())
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(self.named_segments())
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(self.named_segments())
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.single()?.deserialize_identifier(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Access to segments as a sequence or as a map from segment names to values.
+struct Segments<'b, I> {
+    segments: I,
+    /// The segment whose name was just deserialized as a map key.
+    value: Option<(Cow<'b, str>, &'b str)>,
+}
+
+impl<'de, 'b, I> de::SeqAccess<'de> for Segments<'b, I>
+where
+    I: Iterator<Item = (Cow<'b, str>, &'b str)>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.segments.next() {
+            Some((name, value)) => {
+                let segment = SegmentDeserializer::new(name, value)?;
+                seed.deserialize(segment).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'de, 'b, I> de::MapAccess<'de> for Segments<'b, I>
+where
+    I: Iterator<Item = (Cow<'b, str>, &'b str)>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.segments.next() {
+            Some((name, value)) => {
+                let key = seed.deserialize(name.clone().into_owned().into_deserializer())?;
+                self.value = Some((name, value));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, value) = self
+            .value
+            .take()
+            .ok_or_else(|| Error("value requested before key".to_owned()))?;
+        seed.deserialize(SegmentDeserializer::new(name, value)?)
+    }
+}
+
+/// Deserializes the percent-decoded value of a single segment.
+struct SegmentDeserializer<'b> {
+    name: Cow<'b, str>,
+    value: String,
+}
+
+impl<'b> SegmentDeserializer<'b> {
+    fn new(name: Cow<'b, str>, value: &str) -> Result<SegmentDeserializer<'b>, Error> {
+        match decode_segment(value) {
+            Some(value) => Ok(SegmentDeserializer {
+                name,
+                value: value.into_owned(),
+            }),
+            None => Err(Error(format!(
+                "segment `{}` is not valid UTF-8 or contains an encoded slash after decoding",
+                name
+            ))),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T, Error> {
+        self.value.parse().map_err(|_| {
+            Error(format!(
+                "invalid value `{}` for segment `{}`, expected {}",
+                self.value, self.name, expected
+            ))
+        })
+    }
+}
+
+macro_rules! parse_segment {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse::<$ty>(stringify!($ty))?)
+            }
+        )*
+    };
+}
+
+impl<'de, 'b> de::Deserializer<'de> for SegmentDeserializer<'b> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.value)
+    }
+
+    parse_segment! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_char => visit_char(char),
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.value.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
@@ -0,0 +1,249 @@
+use http::status::StatusCode;
+use http_service::Body;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{IntoResponse, Response};
+
+/// The reason an extractor failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RejectionKind {
+    /// A required part of the request, such as a header, is absent.
+    Missing,
+    /// A part of the request is present but could not be parsed or deserialized.
+    Malformed,
+    /// The request body could not be read.
+    Body,
+    /// The request body exceeds the configured `body::SizeLimit`.
+    TooLarge,
+    /// The request body has a media type the extractor does not accept.
+    UnsupportedMediaType,
+    /// The extractor is not usable for the route, e.g. a `Subdomain` outside of a wildcard host.
+    Internal,
+}
+
+impl RejectionKind {
+    /// The default status of responses for this kind of rejection.
+    pub fn status(self) -> StatusCode {
+        match self {
+            RejectionKind::Missing | RejectionKind::Malformed | RejectionKind::Body => {
+                StatusCode::BAD_REQUEST
+            }
+            RejectionKind::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            RejectionKind::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            RejectionKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// A short identifier of the kind, such as `malformed`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectionKind::Missing => "missing",
+            RejectionKind::Malformed => "malformed",
+            RejectionKind::Body => "body",
+            RejectionKind::TooLarge => "too_large",
+            RejectionKind::UnsupportedMediaType => "unsupported_media_type",
+            RejectionKind::Internal => "internal",
+        }
+    }
+}
+
+/// The failure of an extractor.
+///
+/// A rejection converts into a plain text response with the status of its kind and the message as
+/// body. The rejection itself is kept in the `extensions` of that response, so that a
+/// `RenderRejection` configured for the endpoint can render it differently.
+///
+/// Custom extractors can fail with a rejection to get the same treatment:
+///
+/// ```
+/// use tide::{IntoResponse, Rejection, RejectionKind};
+///
+/// let resp = Rejection::new(RejectionKind::Missing, "ApiKey", "Missing header `x-api-key`")
+///     .into_response();
+/// assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+/// assert!(resp.extensions().get::<Rejection>().is_some());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rejection {
+    kind: RejectionKind,
+    extractor: &'static str,
+    message: String,
+}
+
+impl Rejection {
+    /// A rejection of the given `kind` by `extractor`, described by `message`.
+    pub fn new(kind: RejectionKind, extractor: &'static str, message: impl Into<String>) -> Self {
+        Rejection {
+            kind,
+            extractor,
+            message: message.into(),
+        }
+    }
+
+    /// The reason the extractor failed.
+    pub fn kind(&self) -> RejectionKind {
+        self.kind
+    }
+
+    /// The name of the extractor, such as `body::Json`.
+    pub fn extractor(&self) -> &'static str {
+        self.extractor
+    }
+
+    /// A description of the failure, including the underlying error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.kind.status()
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let mut resp = self
+            .message
+            .clone()
+            .with_status(self.status())
+            .into_response();
+        resp.extensions_mut().insert(self);
+        resp
+    }
+}
+
+/// A renderer for the responses of failed extractors.
+///
+/// The renderer is a configuration item, so it can be set for the whole app with `App::config`,
+/// for a subrouter with `Router::config`, or for a single endpoint with `EndpointData::config`.
+/// Without a renderer, rejections are answered with their message as plain text.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::RenderRejection;
+///
+/// let mut app = tide::App::new(());
+/// app.config(RenderRejection::html());
+/// app.at("/api").nest(|router| {
+///     router.config(RenderRejection::json());
+/// });
+/// ```
+#[derive(Clone)]
+pub struct RenderRejection(Arc<dyn Fn(&Rejection) -> Response + Send + Sync>);
+
+impl RenderRejection {
+    /// A renderer calling `render`.
+    pub fn new(render: impl Fn(&Rejection) -> Response + Send + Sync + 'static) -> Self {
+        RenderRejection(Arc::new(render))
+    }
+
+    /// Render rejections as a JSON object with the fields `error`, `extractor` and `message`.
+    pub fn json() -> Self {
+        RenderRejection::new(|rejection| {
+            let body = serde_json::json!({
+                "error": rejection.kind().as_str(),
+                "extractor": rejection.extractor(),
+                "message": rejection.message(),
+            });
+            http::Response::builder()
+                .status(rejection.status())
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string().into_bytes()))
+                .unwrap()
+        })
+    }
+
+    /// Render rejections as a minimal HTML page.
+    pub fn html() -> Self {
+        RenderRejection::new(|rejection| {
+            let status = rejection.status();
+            let body = format!(
+                "<!DOCTYPE html>\n<html><head><title>{0} {1}</title></head>\
+                 <body><h1>{0} {1}</h1><p>{2}</p></body></html>\n",
+                status.as_u16(),
+                status.canonical_reason().unwrap_or(""),
+                escape_html(rejection.message()),
+            );
+            http::Response::builder()
+                .status(status)
+                .header("Content-Type", "text/html; charset=utf-8")
+                .body(Body::from(body.into_bytes()))
+                .unwrap()
+        })
+    }
+}
+
+impl fmt::Debug for RenderRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RenderRejection")
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render the response of a failed extractor with `renderer`, if it holds a rejection.
+pub(crate) fn render(resp: Response, renderer: Option<&RenderRejection>) -> Response {
+    let rendered = renderer.and_then(|renderer| {
+        resp.extensions()
+            .get::<Rejection>()
+            .map(|rejection| (renderer.0)(rejection))
+    });
+    rendered.unwrap_or(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection() -> Rejection {
+        Rejection::new(RejectionKind::Malformed, "body::Json", "Invalid JSON body: <eof>")
+    }
+
+    #[test]
+    fn plain_response() {
+        let resp = render(rejection().into_response(), None);
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["Content-Type"], "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn rendered_response() {
+        let resp = render(rejection().into_response(), Some(&RenderRejection::json()));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()["Content-Type"], "application/json");
+
+        let resp = render(rejection().into_response(), Some(&RenderRejection::html()));
+        assert_eq!(resp.headers()["Content-Type"], "text/html; charset=utf-8");
+
+        let resp = render(
+            StatusCode::NOT_FOUND.into_response(),
+            Some(&RenderRejection::json()),
+        );
+        assert!(resp.headers().get("Content-Type").is_none());
+    }
+}
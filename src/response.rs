@@ -1,6 +1,7 @@
 use http_service::Body;
 
 use crate::body;
+use crate::headers::TypedHeader;
 
 /// An HTTP response.
 ///
@@ -25,6 +26,25 @@ pub trait IntoResponse: Send + Sized {
             status,
         }
     }
+
+    /// Create a new `IntoResponse` value that will respond with the given typed header, replacing
+    /// any value set before.
+    ///
+    /// If the header cannot be encoded, e.g. because it contains a line break, the response is
+    /// replaced by an empty `500 Internal Server Error` response.
+    ///
+    /// ```
+    /// # use tide::{headers::ContentType, IntoResponse};
+    /// let resp = "{}".with_header(ContentType::json()).into_response();
+    /// assert_eq!(resp.headers()["Content-Type"], "application/json");
+    /// ```
+    fn with_header<H: TypedHeader>(self, header: H) -> WithHeader<Self> {
+        WithHeader {
+            inner: self,
+            name: H::name(),
+            value: header.encode(),
+        }
+    }
 }
 
 impl IntoResponse for () {
@@ -115,6 +135,25 @@ impl<R: IntoResponse> IntoResponse for WithStatus<R> {
     }
 }
 
+/// A response type that sets a header.
+pub struct WithHeader<R> {
+    inner: R,
+    name: http::header::HeaderName,
+    value: Result<http::header::HeaderValue, http::header::InvalidHeaderValue>,
+}
+
+impl<R: IntoResponse> IntoResponse for WithHeader<R> {
+    fn into_response(self) -> Response {
+        let value = match self.value {
+            Ok(value) => value,
+            Err(_) => return http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        let mut resp = self.inner.into_response();
+        resp.headers_mut().insert(self.name, value);
+        resp
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +177,23 @@ mod tests {
         let resp = String::from("foo").into_response();
         assert_eq!(resp.headers()["Content-Type"], "text/plain; charset=utf-8");
     }
+
+    #[test]
+    fn typed_header() {
+        let resp = String::from("<p>foo</p>")
+            .with_header(crate::headers::ContentType::html())
+            .with_status(http::status::StatusCode::CREATED)
+            .into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::CREATED);
+        assert_eq!(resp.headers()["Content-Type"], "text/html; charset=utf-8");
+
+        let resp = "foo"
+            .with_header(crate::headers::UserAgent("a\r\nb".to_owned()))
+            .into_response();
+        assert_eq!(
+            resp.status(),
+            http::status::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert!(resp.headers().get("User-Agent").is_none());
+    }
 }
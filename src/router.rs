@@ -1,52 +1,302 @@
 use std::any::Any;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use futures::future;
+use http_service::HttpService;
+
 use crate::{
     configuration::Store,
     endpoint::{BoxedEndpoint, Endpoint},
-    Middleware,
+    guard::Guard,
+    head::Head,
+    mount::Mount,
+    urls::{join_path, Urls},
+    Extract, IntoResponse, Middleware, Rejection, RejectionKind, Request, Response,
+};
+use path_table::RouteMatch;
+
+mod table;
+
+use self::table::PathTable;
+
+pub(crate) use self::table::{
+    constraint_matcher, parse_wildcard, template_segments, wildcard_names, Matcher, Matchers,
 };
-use path_table::{PathTable, RouteMatch};
 
 /// A core type for routing.
 ///
 /// The `Router` type can be used to set up routes and resources, and to apply middleware.
+#[derive(Clone)]
 pub struct Router<Data> {
     table: PathTable<ResourceData<Data>>,
     middleware_base: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
     pub(crate) store_base: Store,
+    /// Incremented by `config`, so that updates of a running server can tell whether the
+    /// defaults changed.
+    pub(crate) config_version: u64,
+    prefix: String,
+    urls: Urls,
+    matchers: Matchers,
+    origin: Option<Arc<str>>,
 }
 
 pub(crate) struct RouteResult<'a, Data> {
     pub(crate) endpoint: &'a EndpointData<Data>,
     pub(crate) params: Option<RouteMatch<'a>>,
-    pub(crate) middleware: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
+    pub(crate) middleware: Cow<'a, [Arc<dyn Middleware<Data> + Send + Sync>]>,
+    pub(crate) allowed: Option<AllowedMethods>,
+    pub(crate) redirect: Option<TrailingSlashRedirect>,
+    pub(crate) rejected: Option<GuardRejection>,
+}
+
+/// The endpoints a router falls back to when a request does not match any of its endpoints.
+pub(crate) struct Fallbacks<Data> {
+    /// Handles requests whose path does not match any resource.
+    pub(crate) not_found: EndpointData<Data>,
+    /// Handles requests whose path matches a resource that has no endpoint for the method.
+    pub(crate) method_not_allowed: EndpointData<Data>,
+    /// Handles `OPTIONS` requests for resources without an explicit `OPTIONS` endpoint.
+    pub(crate) options: EndpointData<Data>,
+    /// Redirects requests whose trailing slash differs from the matched resource.
+    pub(crate) redirect: EndpointData<Data>,
+    /// Handles requests rejected by the guards of all endpoints for their method.
+    pub(crate) rejected: EndpointData<Data>,
+}
+
+impl<Data: Clone + Send + Sync + 'static> Fallbacks<Data> {
+    pub(crate) fn new() -> Fallbacks<Data> {
+        Fallbacks {
+            not_found: EndpointData::new(
+                BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
+                &[],
+            ),
+            method_not_allowed: EndpointData::new(
+                BoxedEndpoint::new(async || http::status::StatusCode::METHOD_NOT_ALLOWED),
+                &[],
+            ),
+            options: EndpointData::new(
+                BoxedEndpoint::new(async || http::status::StatusCode::OK),
+                &[],
+            ),
+            redirect: EndpointData::new(BoxedEndpoint::new(redirect_trailing_slash), &[]),
+            rejected: EndpointData::new(
+                BoxedEndpoint::new(async move |rejection: GuardRejection| rejection.0),
+                &[],
+            ),
+        }
+    }
+}
+
+/// The policy for request paths whose trailing slash differs from the path of the resource.
+///
+/// The policy is a configuration item, so it can be set for the whole app with `App::config`, or
+/// for a subrouter with `Router::config`. Resources whose path ends with a `*` wildcard are not
+/// affected.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::TrailingSlash;
+///
+/// let mut app = tide::App::new(());
+/// app.config(TrailingSlash::Redirect(http::StatusCode::PERMANENT_REDIRECT));
+/// app.at("/articles").get(async || "articles"); // `/articles/` redirects to `/articles`
+/// app.at("/docs/").nest(|router| {
+///     router.config(TrailingSlash::Strict);
+///     router.at("/intro/").get(async || "intro"); // `/docs/intro` is not found
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Treat `/foo` and `/foo/` as the same path. This is the default.
+    Equivalent,
+    /// Only match the form used in the path of the resource.
+    Strict,
+    /// Redirect to the form used in the path of the resource, with the given status code (usually
+    /// `301 Moved Permanently` or `308 Permanent Redirect`).
+    Redirect(http::StatusCode),
+}
+
+impl Default for TrailingSlash {
+    fn default() -> Self {
+        TrailingSlash::Equivalent
+    }
+}
+
+/// The response of an extractor used outside of the routing situation it is meant for.
+fn internal(extractor: &'static str, message: &str) -> Response {
+    Rejection::new(RejectionKind::Internal, extractor, message).into_response()
+}
+
+/// A marker in the request `extensions`, requesting a redirect to the canonical form of the path.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TrailingSlashRedirect(http::StatusCode);
+
+impl<S: 'static> Extract<S> for TrailingSlashRedirect {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<TrailingSlashRedirect>() {
+            Some(redirect) => future::ok(*redirect),
+            None => future::err(internal(
+                "TrailingSlashRedirect",
+                "The request was not routed to a trailing slash redirect",
+            )),
+        }
+    }
+}
+
+async fn redirect_trailing_slash(head: Head, redirect: TrailingSlashRedirect) -> Response {
+    // Routing skips empty segments, so the location is built from the non-empty ones. Repeated
+    // slashes are never passed on, as `//host` would redirect to another host.
+    let path = head.path();
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let mut location = format!("/{}", segments.join("/"));
+    if !path.ends_with('/') && !segments.is_empty() {
+        location.push('/');
+    }
+    if let Some(query) = head.query() {
+        location.push('?');
+        location.push_str(query);
+    }
+
+    http::Response::builder()
+        .status(redirect.0)
+        .header(http::header::LOCATION, location.as_str())
+        .body(http_service::Body::empty())
+        .unwrap()
+}
+
+/// The status of a request rejected by guards, stored in the request `extensions`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GuardRejection(http::StatusCode);
+
+impl<S: 'static> Extract<S> for GuardRejection {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<GuardRejection>() {
+            Some(rejection) => future::ok(*rejection),
+            None => future::err(internal(
+                "GuardRejection",
+                "The request was not rejected by guards",
+            )),
+        }
+    }
 }
 
 fn route_match_success<'a, Data>(
+    router: &'a Router<Data>,
+    path: &'a str,
     route: &'a ResourceData<Data>,
-    route_match: RouteMatch<'a>,
-    method: &http::Method,
+    segments: Vec<&'a str>,
+    req: &Request,
+    fallbacks: &'a Fallbacks<Data>,
 ) -> Option<RouteResult<'a, Data>> {
     // If it is a HTTP HEAD request then check if there is a callback in the endpoints map
-    // if not then fallback to the behavior of HTTP GET else proceed as usual
-    let endpoint =
-        if method == http::Method::HEAD && !route.endpoints.contains_key(&http::Method::HEAD) {
-            route.endpoints.get(&http::Method::GET)?
-        } else {
-            route.endpoints.get(method)?
-        };
-    let middleware = &*route.middleware;
-
+    // if not then fallback to the behavior of HTTP GET else proceed as usual. Likewise, an
+    // OPTIONS request without a callback is answered with the methods of the resource.
+    let method = req.method();
+    let selected = match route.select(method, req) {
+        None if method == http::Method::HEAD => route.select(&http::Method::GET, req),
+        selected => selected,
+    };
+    let (endpoint, middleware, allowed) = match selected {
+        Some(Ok(endpoint)) => (endpoint, Cow::Borrowed(&*endpoint.middleware), None),
+        _ if route.any.is_some() => {
+            let endpoint = route.any.as_ref().unwrap();
+            (endpoint, Cow::Borrowed(&*endpoint.middleware), None)
+        }
+        Some(Err(status)) => return Some(route_rejected(router, path, route, status, fallbacks)),
+        None if method == http::Method::OPTIONS => {
+            let middleware = fallback_middleware(&route.middleware, &fallbacks.options);
+            (&fallbacks.options, middleware, Some(route.allowed_methods()))
+        }
+        None => return None,
+    };
+
+    // Endpoints merged at the same resource may name its wildcards differently.
+    let route_match = RouteMatch {
+        map: endpoint
+            .segment_names
+            .iter()
+            .zip(&segments)
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, segment)| (&**name, *segment))
+            .collect(),
+        vec: segments,
+    };
     Some(RouteResult {
         endpoint,
         params: Some(route_match),
         middleware,
+        allowed,
+        redirect: None,
+        rejected: None,
     })
 }
 
+fn route_rejected<'a, Data>(
+    router: &'a Router<Data>,
+    path: &'a str,
+    route: &'a ResourceData<Data>,
+    status: http::StatusCode,
+    fallbacks: &'a Fallbacks<Data>,
+) -> RouteResult<'a, Data> {
+    if status == http::StatusCode::NOT_FOUND {
+        return router.route_not_found(path, fallbacks, &route.middleware);
+    }
+    RouteResult {
+        endpoint: &fallbacks.rejected,
+        params: None,
+        middleware: fallback_middleware(&route.middleware, &fallbacks.rejected),
+        allowed: None,
+        redirect: None,
+        rejected: Some(GuardRejection(status)),
+    }
+}
+
+fn route_method_mismatch<'a, Data>(
+    route: &'a ResourceData<Data>,
+    endpoint: &'a EndpointData<Data>,
+) -> RouteResult<'a, Data> {
+    RouteResult {
+        endpoint,
+        params: None,
+        middleware: fallback_middleware(&route.middleware, endpoint),
+        allowed: Some(route.allowed_methods()),
+        redirect: None,
+        rejected: None,
+    }
+}
+
+fn route_redirect<'a, Data>(
+    route: &'a ResourceData<Data>,
+    endpoint: &'a EndpointData<Data>,
+    status: http::StatusCode,
+) -> RouteResult<'a, Data> {
+    RouteResult {
+        endpoint,
+        params: None,
+        middleware: fallback_middleware(&route.middleware, endpoint),
+        allowed: None,
+        redirect: Some(TrailingSlashRedirect(status)),
+        rejected: None,
+    }
+}
+
 fn route_match_failure<'a, Data>(
     endpoint: &'a EndpointData<Data>,
     middleware: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
@@ -54,7 +304,34 @@ fn route_match_failure<'a, Data>(
     RouteResult {
         endpoint,
         params: None,
-        middleware: &*middleware,
+        middleware: fallback_middleware(middleware, endpoint),
+        allowed: None,
+        redirect: None,
+        rejected: None,
+    }
+}
+
+fn route_default<'a, Data>(endpoint: &'a EndpointData<Data>) -> RouteResult<'a, Data> {
+    RouteResult {
+        endpoint,
+        params: None,
+        middleware: Cow::Borrowed(&*endpoint.middleware),
+        allowed: None,
+        redirect: None,
+        rejected: None,
+    }
+}
+
+/// The middleware for a fallback `endpoint` shared by all resources: the middleware `base` of the
+/// resource or router, followed by the middleware of the endpoint itself.
+fn fallback_middleware<'a, Data>(
+    base: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
+    endpoint: &'a EndpointData<Data>,
+) -> Cow<'a, [Arc<dyn Middleware<Data> + Send + Sync>]> {
+    if endpoint.middleware.is_empty() {
+        Cow::Borrowed(base)
+    } else {
+        Cow::Owned(base.iter().chain(&endpoint.middleware).cloned().collect())
     }
 }
 
@@ -78,15 +355,20 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// other hand extracts and parses the respective part of the path of the incoming request to
     /// pass it along to the endpoint as an argument. A wildcard segment is either defined by "{}"
     /// or by "{name}" for a so called named wildcard segment which can be extracted using
-    /// `NamedSegment`. It is not possible to define wildcard segments with different names for
-    /// otherwise identical paths.
+    /// `NamedSegment`.
+    ///
+    /// A wildcard segment can be restricted by a *constraint* following a colon, e.g. "{id:u64}"
+    /// or "{:u64}". A constraint is either the name of a matcher or a regular expression that has
+    /// to match the whole segment, e.g. "{slug:[a-z-]+}". The built-in matchers are the integer
+    /// types, `f32`, `f64` and `bool`, matching segments that parse as the respective type. More
+    /// matchers can be registered with [`Router::matcher`](#method.matcher). Constraints are
+    /// checked against the percent-decoded segment, the same value `Path` and `Named` extract.
     ///
     /// Wildcard definitions can be followed by an optional *wildcard modifier*. Currently, there is
     /// only one modifier: `*`, which means that the wildcard will match to the end of given path,
     /// no matter how many segments are left, even nothing. If there is a modifier for unnamed
     /// wildcard definition, `{}` may be omitted. That is, `{}*` can be written as `*`. It is an
-    /// error to define two wildcard segments with different wildcard modifiers, or to write other
-    /// path segment after a segment with wildcard modifier.
+    /// error to write other path segment after a segment with wildcard modifier.
     ///
     /// Here are some examples omitting the HTTP verb based endpoint selection:
     ///
@@ -100,31 +382,127 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// app.at("single_page_app/*");
     /// ```
     ///
-    /// Notice that there is no fallback route matching, i.e. either a resource is a full match or
-    /// not, which means that the order of adding resources has no effect.
+    /// When several resources could match a segment, concrete segments are tried first, then
+    /// constrained wildcards, then unconstrained wildcards and finally wildcards with the `*`
+    /// modifier. If a candidate does not lead to a resource for the rest of the path, the next one
+    /// is tried. This way, `/users/{id:u64}` and `/users/{name}` can coexist, and `/users/me`
+    /// never reaches an endpoint expecting a number:
+    ///
+    /// ```rust,no_run
+    /// # let mut app = tide::App::new(());
+    /// app.at("/users/me");
+    /// app.at("/users/{id:u64}");
+    /// app.at("/users/{slug:[a-z-]+}");
+    /// ```
+    ///
+    /// Among wildcards of the same kind, the one added first is tried first. Apart from that, the
+    /// order of adding resources has no effect.
+    ///
+    /// Wildcard segments that only differ in their name, as in `/users/{id}` and
+    /// `/users/{user_id}`, denote the same resource. Each endpoint extracts the segments by the
+    /// names of the path it was added at.
     pub fn at<'a>(&'a mut self, path: &'a str) -> Resource<'a, Data> {
-        let table = self.table.setup_table(path);
+        let table = self.table.setup_table(path, &self.matchers);
         Resource {
             table,
             middleware_base: &self.middleware_base,
+            urls: &mut self.urls,
+            matchers: &self.matchers,
+            origin: &self.origin,
+            path: join_path(&self.prefix, path),
         }
     }
 
-    /// Create a new top-level router.
-    pub(crate) fn new() -> Router<Data> {
+    /// Register a named `matcher` for constraining wildcard segments.
+    ///
+    /// Afterwards, `{segment:name}` in paths of this router and its subrouters only matches
+    /// segments for which `matcher` returns `true`. Matchers have to be registered before the
+    /// paths using them.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # let mut app = tide::App::new(());
+    /// app.router().matcher("hex", |s: &str| s.chars().all(|c| c.is_ascii_hexdigit()));
+    /// app.at("/colors/{color:hex}").get(async || "a color");
+    /// ```
+    pub fn matcher(
+        &mut self,
+        name: &str,
+        matcher: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.matchers
+            .insert(name.to_owned(), Arc::new(matcher) as Matcher);
+        self
+    }
+
+    /// Create a new, standalone router.
+    ///
+    /// A router can be set up independently of an app, e.g. by a module owning a group of routes,
+    /// and later be added to an app or another router with [`Resource::merge`]. Its paths are
+    /// relative to the resource it is merged into.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// fn users() -> tide::Router<()> {
+    ///     let mut router = tide::Router::new();
+    ///     router.origin("users");
+    ///     router.at("/").get(async || "all users");
+    ///     router.at("/{id:u64}").get(async || "one user");
+    ///     router
+    /// }
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/users").merge(users()).unwrap();
+    /// ```
+    ///
+    /// [`Resource::merge`]: struct.Resource.html#method.merge
+    pub fn new() -> Router<Data> {
         Router {
             table: PathTable::new(),
             middleware_base: Vec::new(),
             store_base: Store::new(),
+            config_version: 0,
+            prefix: String::new(),
+            urls: Urls::new(),
+            matchers: Matchers::new(),
+            origin: None,
         }
     }
 
+    /// Label the routes of this router with their `origin`, e.g. the name of the module that
+    /// defines them.
+    ///
+    /// The label is used to report conflicting routes when merging routers with
+    /// [`Resource::merge`](struct.Resource.html#method.merge).
+    pub fn origin(&mut self, origin: &str) -> &mut Self {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    /// Create a new top-level router, starting out with the middleware applied to this router.
+    pub(crate) fn new_sibling(&self) -> Router<Data> {
+        let mut router = Router::new();
+        router.middleware_base = self.middleware_base.clone();
+        router.matchers = self.matchers.clone();
+        router
+    }
+
+    /// The named routes of this router and its subrouters.
+    ///
+    /// See [`Urls`](struct.Urls.html) for generating URLs from them.
+    pub fn urls(&self) -> &Urls {
+        &self.urls
+    }
+
     /// Apply `middleware` to this router.
     ///
     /// Note that the order of nesting subrouters and applying middleware matters. If there are
     /// nested subrouters *before* the method call, the given middleware will be applied *after*
     /// the subrouter middleware.
     ///
+    /// Middleware of the router always runs before middleware added to a single resource or
+    /// endpoint with `Resource::middleware` or `EndpointData::middleware`.
+    ///
     /// ```
     /// # #![feature(futures_api, async_await)]
     /// # fn passthrough_middleware<Data: Clone + Send>(
@@ -148,9 +526,9 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// });
     /// ```
     pub fn middleware(&mut self, middleware: impl Middleware<Data> + 'static) -> &mut Self {
-        let middleware = Arc::new(middleware);
+        let middleware: Arc<dyn Middleware<Data> + Send + Sync> = Arc::new(middleware);
         for resource in self.table.iter_mut() {
-            resource.middleware.push(middleware.clone());
+            resource.push_router_middleware(&middleware);
         }
         self.middleware_base.push(middleware);
         self
@@ -161,32 +539,180 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// The default configuration will be applied when the router setup ends.
     pub fn config<T: Any + Debug + Clone + Send + Sync>(&mut self, item: T) -> &mut Self {
         self.store_base.write(item);
+        self.config_version += 1;
         self
     }
 
+    /// Set the default handler for this router, handling requests to any path beneath its prefix
+    /// that does not match a resource.
+    ///
+    /// This is most useful for subrouters: an API nested at `/api` can answer unknown paths with a
+    /// JSON error, while the rest of the app keeps using the app's default handler. If several
+    /// nested routers have a default handler, the one of the innermost router containing the
+    /// requested path is used. The middleware of the router applies to its default handler.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// let mut app = tide::App::new(());
+    /// app.at("/").get(async || "index");
+    /// app.at("/api").nest(|router| {
+    ///     router.at("/items").get(async || "items");
+    ///     router.default_handler(async || r#"{"error":"not found"}"#);
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the router already has a default handler.
+    pub fn default_handler<T: Endpoint<Data, U>, U>(
+        &mut self,
+        handler: T,
+    ) -> &mut EndpointData<Data> {
+        let path = join_path(&self.prefix, "*");
+        let table = self.table.setup_table("*", &self.matchers);
+        let resource = resource_data(table, path, &self.middleware_base);
+        if resource.not_found.is_some() {
+            panic!("A default handler is already set for this router");
+        }
+
+        let endpoint = EndpointData::new(BoxedEndpoint::new(handler), &resource.middleware);
+        resource.not_found = Some(endpoint);
+        resource.not_found.as_mut().unwrap()
+    }
+
+    /// Select the endpoint for a request.
+    ///
+    /// Requests whose path matches no resource are given to the default handler of the innermost
+    /// router containing the path, or `fallbacks.not_found` if there is none. If the path
+    /// matches but the resource has no endpoint for `method`, the request is given to
+    /// `fallbacks.method_not_allowed` instead, and the result carries the methods the resource
+    /// does accept. `OPTIONS` requests to a resource without an `OPTIONS` endpoint are answered
+    /// by `fallbacks.options` in the same way.
+    ///
+    /// If the trailing slash of `path` differs from the resource, the `TrailingSlash` policy of the
+    /// resource decides whether it is matched, handled like an unmatched path, or redirected by
+    /// `fallbacks.redirect`.
+    ///
+    /// Among several endpoints for the method of `req`, the first one whose guards accept `req` is
+    /// selected. If all are rejected, the request is given to `fallbacks.rejected`, or handled
+    /// like an unmatched path if the rejecting guard asks for `404 Not Found`.
     pub(crate) fn route<'a>(
         &'a self,
         path: &'a str,
-        method: &http::Method,
-        default_handler: &'a Arc<EndpointData<Data>>,
+        req: &Request,
+        fallbacks: &'a Fallbacks<Data>,
     ) -> RouteResult<'a, Data> {
+        let method = req.method();
         match self.table.route(path) {
-            Some((route, route_match)) => route_match_success(route, route_match, method)
-                .unwrap_or_else(|| route_match_failure(default_handler, &self.middleware_base)),
-            None => route_match_failure(default_handler, &self.middleware_base),
+            Some((route, _)) if !route.has_endpoints() => match &route.not_found {
+                Some(endpoint) => route_default(endpoint),
+                None => self.route_not_found(path, fallbacks, &self.middleware_base),
+            },
+            Some((route, segments)) => {
+                if route.trailing_slash_differs(path) {
+                    match route.trailing_slash(method) {
+                        TrailingSlash::Equivalent => {}
+                        TrailingSlash::Strict => {
+                            return self.route_not_found(path, fallbacks, &self.middleware_base)
+                        }
+                        TrailingSlash::Redirect(status) => {
+                            return route_redirect(route, &fallbacks.redirect, status)
+                        }
+                    }
+                }
+
+                route_match_success(self, path, route, segments, req, fallbacks)
+                    .unwrap_or_else(|| route_method_mismatch(route, &fallbacks.method_not_allowed))
+            }
+            None => route_match_failure(&fallbacks.not_found, &self.middleware_base),
         }
     }
 }
 
+impl<Data: Clone + Send + Sync + 'static> Default for Router<Data> {
+    fn default() -> Router<Data> {
+        Router::new()
+    }
+}
+
 impl<Data> Router<Data> {
+    /// Give a request to `path` to the default handler of the innermost router containing the
+    /// path, or to `fallbacks.not_found` with the middleware `base` if there is none.
+    fn route_not_found<'a>(
+        &'a self,
+        path: &'a str,
+        fallbacks: &'a Fallbacks<Data>,
+        base: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
+    ) -> RouteResult<'a, Data> {
+        let default = self
+            .table
+            .route_filtered(path, |resource| resource.not_found.is_some())
+            .and_then(|(route, _)| route.not_found.as_ref());
+        match default {
+            Some(endpoint) => route_default(endpoint),
+            None => route_match_failure(&fallbacks.not_found, base),
+        }
+    }
+
+    /// List the resources registered with this router, ordered by path.
+    ///
+    /// This is useful for printing the routes of an app at startup, or for checking in tests that
+    /// the expected endpoints are present.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// let mut app = tide::App::new(());
+    /// app.at("/items").get(async || "items");
+    /// app.at("/items/{id:u64}").get(async || "item").config(42usize);
+    ///
+    /// for route in app.router().routes() {
+    ///     println!("{} {:?}", route.path(), route.methods());
+    /// }
+    ///
+    /// let route = app.router().routes().last().unwrap();
+    /// assert_eq!(route.path(), "/items/{id:u64}");
+    /// assert_eq!(route.methods(), vec![&http::Method::GET]);
+    /// assert_eq!(route.store(&http::Method::GET).unwrap().read::<usize>(), Some(&42));
+    /// ```
+    pub fn routes(&self) -> impl Iterator<Item = RouteInfo<'_, Data>> {
+        let mut routes: Vec<_> = self
+            .table
+            .iter()
+            .filter(|resource| resource.has_endpoints())
+            .map(|resource| RouteInfo {
+                resource,
+                store_base: &self.store_base,
+            })
+            .collect();
+        routes.sort_by(|a, b| a.path().cmp(b.path()));
+        routes.into_iter()
+    }
+
+    /// Apply the defaults of this subrouter to its endpoints for good.
     pub(crate) fn apply_default_config(&mut self) {
         for resource in self.table.iter_mut() {
-            for endpoint in resource.endpoints.values_mut() {
+            for endpoint in resource.endpoints_mut() {
+                endpoint.config.merge(&self.store_base);
                 endpoint.store.merge(&self.store_base);
             }
         }
     }
 
+    /// Derive the configuration of all endpoints from their own configuration and the defaults of
+    /// this top-level router, which in turn default to `defaults`.
+    ///
+    /// Unlike `apply_default_config`, this can be repeated after the defaults changed.
+    pub(crate) fn derive_config(&mut self, defaults: &Store) {
+        let mut base = self.store_base.clone();
+        base.merge(defaults);
+        for resource in self.table.iter_mut() {
+            for endpoint in resource.endpoints_mut() {
+                endpoint.store = endpoint.config.clone();
+                endpoint.store.merge(&base);
+            }
+        }
+    }
+
     pub(crate) fn get_item<T: Any + Debug + Clone + Send + Sync>(&self) -> Option<&T> {
         self.store_base.read()
     }
@@ -195,35 +721,496 @@ impl<Data> Router<Data> {
 /// A handle to the endpoint.
 ///
 /// This can be used to add configuration items to the endpoint.
+#[derive(Clone)]
 pub struct EndpointData<Data> {
     pub(crate) endpoint: BoxedEndpoint<Data>,
+    /// The configuration of the endpoint, including all defaults.
     pub(crate) store: Store,
+    /// The configuration of the endpoint and its subrouters, without the defaults of the top-level
+    /// router.
+    config: Store,
+    /// The middleware of the endpoint, including that of its resource and enclosing routers.
+    pub(crate) middleware: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+    /// The number of entries at the end of `middleware` added to this endpoint only.
+    local_middleware: usize,
+    /// The origin of the router the endpoint was merged from, if any.
+    origin: Option<Arc<str>>,
+    /// The guards that have to accept a request for the endpoint to handle it.
+    guards: Vec<Arc<dyn Guard>>,
+    /// The names of the wildcard segments of the path the endpoint was added at.
+    segment_names: Vec<String>,
 }
 
 impl<Data> EndpointData<Data> {
+    pub(crate) fn new(
+        endpoint: BoxedEndpoint<Data>,
+        middleware: &[Arc<dyn Middleware<Data> + Send + Sync>],
+    ) -> EndpointData<Data> {
+        EndpointData {
+            endpoint,
+            store: Store::new(),
+            config: Store::new(),
+            middleware: middleware.to_vec(),
+            local_middleware: 0,
+            origin: None,
+            guards: Vec::new(),
+            segment_names: Vec::new(),
+        }
+    }
+
+    /// Only handle requests accepted by `guard`.
+    ///
+    /// If the guards of all endpoints for the method of a request reject it, the response status
+    /// is given by the rejecting guard, e.g. `406 Not Acceptable` for
+    /// [`guard::accept`](guard/fn.accept.html). See the [`guard`](guard/index.html) module for
+    /// details.
+    ///
+    /// Guards only apply to endpoints for explicit methods, not to those added with
+    /// `Resource::any` or `Resource::mount`.
+    pub fn guard(&mut self, guard: impl Guard) -> &mut Self {
+        self.guards.push(Arc::new(guard));
+        self
+    }
+
+    /// Apply `middleware` to this endpoint only.
+    ///
+    /// It runs after the middleware of the router and resource, regardless of the order in which
+    /// they were added.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(futures_api, async_await)]
+    /// # fn authenticate<Data: Clone + Send>(
+    /// #     ctx: tide::middleware::RequestContext<Data>,
+    /// # ) -> futures::future::FutureObj<tide::Response> {
+    /// #     ctx.next()
+    /// # }
+    /// let mut app = tide::App::new(());
+    /// app.at("/items").get(async || "items");
+    /// app.at("/items").post(async || "created").middleware(authenticate);
+    /// ```
+    pub fn middleware(&mut self, middleware: impl Middleware<Data> + 'static) -> &mut Self {
+        self.middleware.push(Arc::new(middleware));
+        self.local_middleware += 1;
+        self
+    }
+
     /// Add a configuration `item` for this endpoint.
     pub fn config<T: Any + Debug + Clone + Send + Sync>(&mut self, item: T) -> &mut Self {
-        self.store.write(item);
+        self.store.write(item.clone());
+        self.config.write(item);
         self
     }
 }
 
-/// A handle to a resource (identified by a path).
+/// A handle to a resource (identified by a path).
+///
+/// All HTTP requests are made against resources. After using `Router::at` (or `App::at`) to
+/// establish a resource path, the `Resource` type can be used to establish endpoints for various
+/// HTTP methods at that path. Also, using `nest`, it can be used to set up a subrouter.
+///
+/// After establishing an endpoint, the method will return `&mut EndpointData`. This can be used to
+/// set per-endpoint configuration.
+pub struct Resource<'a, Data> {
+    table: &'a mut PathTable<ResourceData<Data>>,
+    middleware_base: &'a Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+    urls: &'a mut Urls,
+    matchers: &'a Matchers,
+    origin: &'a Option<Arc<str>>,
+    path: String,
+}
+
+#[derive(Clone)]
+struct ResourceData<Data> {
+    /// The full path template of the resource, including the prefixes of enclosing subrouters.
+    path: String,
+    /// The indices into `endpoints` of the endpoints for each method, in order of registration.
+    methods: HashMap<http::Method, Vec<usize>>,
+    /// The endpoints for explicit methods, each of which may handle several methods.
+    endpoints: Vec<EndpointData<Data>>,
+    /// The endpoint for methods without an endpoint of their own.
+    any: Option<EndpointData<Data>>,
+    /// The default handler of the router whose subtree this `*` resource covers.
+    not_found: Option<EndpointData<Data>>,
+    /// The middleware of the resource, including that of enclosing routers.
+    middleware: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+    /// The number of entries at the end of `middleware` added to this resource only.
+    local_middleware: usize,
+}
+
+/// Get the resource of `table`, setting up an empty one at `path` if there is none yet.
+fn resource_data<'a, Data>(
+    table: &'a mut PathTable<ResourceData<Data>>,
+    path: String,
+    middleware_base: &[Arc<dyn Middleware<Data> + Send + Sync>],
+) -> &'a mut ResourceData<Data> {
+    let resource = table.resource_mut();
+    if resource.is_none() {
+        let new_resource = ResourceData {
+            path,
+            methods: HashMap::new(),
+            endpoints: Vec::new(),
+            any: None,
+            not_found: None,
+            middleware: middleware_base.to_vec(),
+            local_middleware: 0,
+        };
+        *resource = Some(new_resource);
+    }
+    resource.as_mut().unwrap()
+}
+
+impl<Data> ResourceData<Data> {
+    /// Whether this resource has endpoints of its own, rather than only holding middleware or the
+    /// default handler of a router.
+    fn has_endpoints(&self) -> bool {
+        !self.methods.is_empty() || self.any.is_some()
+    }
+
+    /// The first endpoint registered for `method`.
+    fn endpoint(&self, method: &http::Method) -> Option<&EndpointData<Data>> {
+        self.methods.get(method).map(|idx| &self.endpoints[idx[0]])
+    }
+
+    /// Select the first endpoint for `method` whose guards accept `req`.
+    ///
+    /// If all endpoints for `method` are rejected, this returns the rejection status of the first
+    /// failing guard of the first endpoint.
+    fn select(
+        &self,
+        method: &http::Method,
+        req: &Request,
+    ) -> Option<Result<&EndpointData<Data>, http::StatusCode>> {
+        let mut rejection = None;
+        for &idx in self.methods.get(method)? {
+            let endpoint = &self.endpoints[idx];
+            match endpoint.guards.iter().find(|guard| !guard.check(req)) {
+                Some(guard) => {
+                    rejection.get_or_insert(guard.rejection());
+                }
+                None => return Some(Ok(endpoint)),
+            }
+        }
+        rejection.map(Err)
+    }
+
+    /// Whether another endpoint can be added for `method`, i.e. all endpoints for it have guards.
+    fn accepts_endpoint(&self, method: &http::Method) -> bool {
+        self.methods.get(method).map_or(true, |idx| {
+            idx.iter().all(|&idx| !self.endpoints[idx].guards.is_empty())
+        })
+    }
+
+    fn endpoints_mut(&mut self) -> impl Iterator<Item = &mut EndpointData<Data>> {
+        self.endpoints
+            .iter_mut()
+            .chain(self.any.as_mut())
+            .chain(self.not_found.as_mut())
+    }
+
+    /// Add `middleware` of an enclosing router, running before the middleware of the resource and
+    /// its endpoints.
+    fn push_router_middleware(&mut self, middleware: &Arc<dyn Middleware<Data> + Send + Sync>) {
+        let local = self.local_middleware;
+        let at = self.middleware.len() - local;
+        self.middleware.insert(at, middleware.clone());
+        for endpoint in self.endpoints_mut() {
+            let at = endpoint.middleware.len() - endpoint.local_middleware - local;
+            endpoint.middleware.insert(at, middleware.clone());
+        }
+    }
+
+    /// Add the middleware `base` of an enclosing router this resource is merged into.
+    fn prepend_middleware(&mut self, base: &[Arc<dyn Middleware<Data> + Send + Sync>]) {
+        fn prepend<T: Clone>(vec: &mut Vec<T>, base: &[T]) {
+            let rest = std::mem::replace(vec, base.to_vec());
+            vec.extend(rest);
+        }
+
+        prepend(&mut self.middleware, base);
+        for endpoint in self.endpoints_mut() {
+            prepend(&mut endpoint.middleware, base);
+        }
+    }
+
+    /// The first endpoint of `other` that this resource already has, with the origin of the
+    /// existing endpoint.
+    fn conflict(&self, other: &ResourceData<Data>) -> Option<(String, Option<Arc<str>>)> {
+        if let Some(method) = other.methods.keys().find(|m| !self.accepts_endpoint(m)) {
+            return Some((method.to_string(), self.endpoint(method).unwrap().origin.clone()));
+        }
+        if let (Some(existing), Some(_)) = (&self.any, &other.any) {
+            return Some(("mounted service".to_owned(), existing.origin.clone()));
+        }
+        if let (Some(existing), Some(_)) = (&self.not_found, &other.not_found) {
+            return Some(("default handler".to_owned(), existing.origin.clone()));
+        }
+        None
+    }
+
+    /// Add the endpoints of `other`, which must not conflict with those of this resource.
+    fn absorb(&mut self, other: ResourceData<Data>) {
+        let offset = self.endpoints.len();
+        for (method, idx) in other.methods {
+            let entry = self.methods.entry(method).or_insert_with(Vec::new);
+            entry.extend(idx.into_iter().map(|idx| idx + offset));
+        }
+        self.endpoints.extend(other.endpoints);
+        self.any = self.any.take().or(other.any);
+        self.not_found = self.not_found.take().or(other.not_found);
+    }
+
+    /// Add `middleware` of this resource, running before the middleware of its endpoints.
+    fn push_middleware(&mut self, middleware: &Arc<dyn Middleware<Data> + Send + Sync>) {
+        self.middleware.push(middleware.clone());
+        self.local_middleware += 1;
+        for endpoint in self.endpoints_mut() {
+            let at = endpoint.middleware.len() - endpoint.local_middleware;
+            endpoint.middleware.insert(at, middleware.clone());
+        }
+    }
+
+    /// Whether `path` ends with a slash while the path of this resource does not, or vice versa.
+    fn trailing_slash_differs(&self, path: &str) -> bool {
+        if self.path == "/" || self.path.ends_with('*') {
+            return false;
+        }
+        let trailing = path.len() > 1 && path.ends_with('/');
+        trailing != self.path.ends_with('/')
+    }
+
+    /// The trailing slash policy for requests with `method`.
+    ///
+    /// This is read from the endpoint for `method`, or any endpoint if there is none.
+    fn trailing_slash(&self, method: &http::Method) -> TrailingSlash {
+        self.endpoint(method)
+            .or_else(|| self.any.as_ref())
+            .or_else(|| self.endpoints.first())
+            .and_then(|endpoint| endpoint.store.read::<TrailingSlash>())
+            .cloned()
+            .unwrap_or_default()
+    }
+    /// The methods this resource has endpoints for, including the implicit `HEAD` for `GET` and
+    /// the implicit `OPTIONS`.
+    fn allowed_methods(&self) -> AllowedMethods {
+        let mut methods: Vec<http::Method> = self.methods.keys().cloned().collect();
+        if self.methods.contains_key(&http::Method::GET)
+            && !self.methods.contains_key(&http::Method::HEAD)
+        {
+            methods.push(http::Method::HEAD);
+        }
+        if !self.methods.contains_key(&http::Method::OPTIONS) {
+            methods.push(http::Method::OPTIONS);
+        }
+        methods.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        AllowedMethods(methods)
+    }
+}
+
+/// A description of a resource registered with a router.
+///
+/// See [`Router::routes`](struct.Router.html#method.routes).
+pub struct RouteInfo<'a, Data> {
+    resource: &'a ResourceData<Data>,
+    store_base: &'a Store,
+}
+
+impl<'a, Data> RouteInfo<'a, Data> {
+    /// The path template of the resource, including the prefixes of enclosing subrouters.
+    pub fn path(&self) -> &'a str {
+        &self.resource.path
+    }
+
+    /// The HTTP methods the resource has endpoints for, ordered by name.
+    pub fn methods(&self) -> Vec<&'a http::Method> {
+        let mut methods: Vec<_> = self.resource.methods.keys().collect();
+        methods.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        methods
+    }
+
+    /// Whether the resource has an endpoint for all methods besides those in `methods`, set
+    /// with `Resource::any` or `Resource::mount`.
+    pub fn accepts_any_method(&self) -> bool {
+        self.resource.any.is_some()
+    }
+
+    /// The configuration of the endpoint for `method`, including the defaults of its router.
+    pub fn store(&self, method: &http::Method) -> Option<Store> {
+        self.resource.endpoint(method).map(|endpoint| {
+            let mut store = endpoint.store.clone();
+            store.merge(self.store_base);
+            store
+        })
+    }
+
+    /// The number of middleware applied to the endpoint for `method`, including those of its
+    /// routers and its resource.
+    pub fn middleware_count(&self, method: &http::Method) -> Option<usize> {
+        self.resource
+            .endpoint(method)
+            .map(|endpoint| endpoint.middleware.len())
+    }
+}
+
+impl<'a, Data> Debug for RouteInfo<'a, Data> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("RouteInfo")
+            .field("path", &self.path())
+            .field("methods", &self.methods())
+            .field("any", &self.accepts_any_method())
+            .field(
+                "middleware",
+                &self
+                    .methods()
+                    .into_iter()
+                    .map(|method| (method, self.middleware_count(method).unwrap_or(0)))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Set the endpoint of `resource` for any method, added at a path with the wildcard segments
+/// `segment_names`.
+fn set_any<Data>(
+    resource: &mut ResourceData<Data>,
+    endpoint: BoxedEndpoint<Data>,
+    segment_names: Vec<String>,
+) -> &mut EndpointData<Data> {
+    if resource.any.is_some() {
+        panic!("An endpoint for any method already exists for this path");
+    }
+
+    let mut endpoint = EndpointData::new(endpoint, &resource.middleware);
+    endpoint.segment_names = segment_names;
+    resource.any = Some(endpoint);
+    resource.any.as_mut().unwrap()
+}
+
+/// Whether the path templates `a` and `b` denote the same resource, ignoring trailing slashes.
+///
+/// Wildcard segments are compared by their constraint and kind only, since `/users/{id}` and
+/// `/users/{user_id}` match the same requests.
+fn same_path(a: &str, b: &str) -> bool {
+    fn normalize(segment: &str) -> (Option<&str>, Option<&str>, bool) {
+        match parse_wildcard(segment) {
+            Some((_, constraint, catch_all)) => (None, constraint, catch_all),
+            None => (Some(segment), None, false),
+        }
+    }
+
+    let a = template_segments(a).into_iter().map(normalize);
+    let b = template_segments(b).into_iter().map(normalize);
+    a.eq(b)
+}
+
+/// An error merging routers, caused by a route that both routers define.
+///
+/// See [`Resource::merge`](struct.Resource.html#method.merge).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteConflict {
+    path: String,
+    endpoint: String,
+    existing: Option<Arc<str>>,
+    merged: Option<Arc<str>>,
+}
+
+impl RouteConflict {
+    /// The path template of the conflicting route.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// What is defined twice: the HTTP method of an endpoint, a mounted service, the default
+    /// handler or the name of the route.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The origin of the route that was already present.
+    pub fn existing_origin(&self) -> Option<&str> {
+        self.existing.as_ref().map(|origin| &**origin)
+    }
+
+    /// The origin of the route that was being merged.
+    pub fn merged_origin(&self) -> Option<&str> {
+        self.merged.as_ref().map(|origin| &**origin)
+    }
+}
+
+impl std::fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let origin = |origin: Option<&str>| match origin {
+            Some(origin) => format!("`{}`", origin),
+            None => "an unnamed router".to_owned(),
+        };
+        write!(
+            f,
+            "conflicting {} for `{}`, defined by {} and {}",
+            self.endpoint,
+            self.path,
+            origin(self.existing_origin()),
+            origin(self.merged_origin()),
+        )
+    }
+}
+
+impl std::error::Error for RouteConflict {}
+
+/// An extractor for the methods accepted by the requested resource.
 ///
-/// All HTTP requests are made against resources. After using `Router::at` (or `App::at`) to
-/// establish a resource path, the `Resource` type can be used to establish endpoints for various
-/// HTTP methods at that path. Also, using `nest`, it can be used to set up a subrouter.
+/// When the path of a request matches a resource that has no endpoint for the request method,
+/// the app answers with `405 Method Not Allowed` and an `Allow` header listing these methods. A
+/// custom handler set with `App::method_not_allowed_handler` can use this extractor to build its
+/// own response; the `Allow` header is added automatically if the handler does not set one.
 ///
-/// After establishing an endpoint, the method will return `&mut EndpointData`. This can be used to
-/// set per-endpoint configuration.
-pub struct Resource<'a, Data> {
-    table: &'a mut PathTable<ResourceData<Data>>,
-    middleware_base: &'a Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::AllowedMethods;
+///
+/// async fn method_not_allowed(allowed: AllowedMethods) -> http::Response<String> {
+///     http::Response::builder()
+///         .status(http::status::StatusCode::METHOD_NOT_ALLOWED)
+///         .body(format!("try one of: {:?}", allowed.0))
+///         .unwrap()
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/items").get(async || "items");
+///     app.method_not_allowed_handler(method_not_allowed);
+///     app.serve()
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AllowedMethods(pub Vec<http::Method>);
+
+impl AllowedMethods {
+    /// Render the methods as the value of an `Allow` header.
+    pub fn header_value(&self) -> http::header::HeaderValue {
+        let methods: Vec<&str> = self.0.iter().map(http::Method::as_str).collect();
+        http::header::HeaderValue::from_str(&methods.join(", "))
+            .expect("HTTP methods are valid header values")
+    }
 }
 
-struct ResourceData<Data> {
-    endpoints: HashMap<http::Method, EndpointData<Data>>,
-    middleware: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+impl<S: 'static> Extract<S> for AllowedMethods {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<AllowedMethods>() {
+            Some(allowed) => future::ok(allowed.clone()),
+            None => future::err(internal(
+                "AllowedMethods",
+                "The request was not routed to a method fallback",
+            )),
+        }
+    }
 }
 
 impl<'a, Data> Resource<'a, Data> {
@@ -239,39 +1226,230 @@ impl<'a, Data> Resource<'a, Data> {
             table: PathTable::new(),
             middleware_base: self.middleware_base.clone(),
             store_base: Store::new(),
+            config_version: 0,
+            prefix: self.path,
+            urls: Urls::new(),
+            matchers: self.matchers.clone(),
+            origin: self.origin.clone(),
         };
         builder(&mut subrouter);
         subrouter.apply_default_config();
         *self.table = subrouter.table;
+        self.urls.extend(subrouter.urls);
+    }
+
+    /// Merge the routes of a separately built `router` into this path.
+    ///
+    /// The paths of `router` are relative to this resource, and the middleware applied to the
+    /// enclosing router runs before the middleware of `router`. Unlike with `nest`, resources that
+    /// are already present are kept, so several routers can be merged at the same path.
+    ///
+    /// # Errors
+    ///
+    /// Fails without changing this router if `router` has an endpoint for a path and method that
+    /// already has one, or a route name that is already taken. The error reports the origins of
+    /// both routes, as set with [`Router::origin`](struct.Router.html#method.origin).
+    pub fn merge(self, mut router: Router<Data>) -> Result<(), RouteConflict> {
+        router.apply_default_config();
+        let mut matchers = self.matchers.clone();
+        matchers.extend(router.matchers.drain());
+        let origin = router.origin.clone();
+        let resources = router.table.into_resources();
+
+        for resource in &resources {
+            let path = join_path(&self.path, &resource.path);
+            let existing = self
+                .table
+                .iter()
+                .find(|existing| same_path(&existing.path, &path));
+            if let Some((endpoint, existing)) = existing.and_then(|e| e.conflict(resource)) {
+                return Err(RouteConflict {
+                    path,
+                    endpoint,
+                    existing: existing.or_else(|| self.origin.clone()),
+                    merged: origin,
+                });
+            }
+        }
+        if let Some(name) = router.urls.names().find(|name| self.urls.template(name).is_some()) {
+            return Err(RouteConflict {
+                path: self.urls.template(name).unwrap().to_owned(),
+                endpoint: format!("name `{}`", name),
+                existing: self.origin.clone(),
+                merged: origin,
+            });
+        }
+
+        let prefix_names = wildcard_names(&self.path);
+        for mut resource in resources {
+            let table = self.table.setup_table(&resource.path, &matchers);
+            resource.path = join_path(&self.path, &resource.path);
+            resource.prepend_middleware(self.middleware_base);
+            for endpoint in resource.endpoints_mut() {
+                if endpoint.origin.is_none() {
+                    endpoint.origin = origin.clone();
+                }
+                let names = std::mem::replace(&mut endpoint.segment_names, prefix_names.clone());
+                endpoint.segment_names.extend(names);
+            }
+
+            let slot = table.resource_mut();
+            match slot {
+                Some(existing) => existing.absorb(resource),
+                None => *slot = Some(resource),
+            }
+        }
+        self.urls.extend_prefixed(router.urls, &self.path, self.matchers);
+        Ok(())
+    }
+
+    /// Give this resource a `name`, so that its URL can be generated with
+    /// [`Urls::url_for`](struct.Urls.html#method.url_for).
+    ///
+    /// Names are global to the app, including those given inside nested subrouters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another resource already has the same name.
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        self.urls.insert(name, &self.path, self.matchers);
+        self
+    }
+
+    /// Mount an HTTP `service` at this path.
+    ///
+    /// All requests to this path or below it are forwarded to `service`, regardless of their
+    /// method. The service sees the request path relative to the mount point, e.g. a request to
+    /// `/legacy/users` is seen as `/users` by a service mounted at `/legacy`. The original URI is
+    /// available in the request `extensions` as [`OriginalUri`](struct.OriginalUri.html).
+    ///
+    /// The middleware of the router applies to mounted services as to any endpoint. Since
+    /// `Server` is an `HttpService` too, this can be used to compose separately built apps.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// let mut legacy = tide::App::new(());
+    /// legacy.at("/users").get(async || "legacy users");
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/legacy").mount(legacy.into_http_service());
+    /// ```
+    pub fn mount<S: HttpService>(&mut self, service: S) -> &mut EndpointData<Data> {
+        let path = join_path(&self.path, "*");
+        let table = self.table.setup_table("*", self.matchers);
+        let names = wildcard_names(&path);
+        let resource = resource_data(table, path, self.middleware_base);
+        set_any(resource, BoxedEndpoint::new(Mount::new(service)), names)
+    }
+
+    /// Add an endpoint for all methods that have no endpoint of their own.
+    ///
+    /// This includes `HEAD` requests if there is no `GET` endpoint, and `OPTIONS` requests, which
+    /// are otherwise answered automatically. The endpoint can use [`Head::method`] to find out
+    /// which method was used.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// use tide::head::Head;
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/echo").any(async move |head: Head| head.method().to_string());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource already has such an endpoint.
+    ///
+    /// [`Head::method`]: head/struct.Head.html#method.method
+    pub fn any<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+        let resource = resource_data(self.table, self.path.clone(), self.middleware_base);
+        set_any(resource, BoxedEndpoint::new(ep), wildcard_names(&self.path))
     }
 
     /// Add an endpoint for the given HTTP method
+    ///
+    /// Besides the standard methods, this accepts extension methods such as WebDAV's `PROPFIND`:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// let propfind = http::Method::from_bytes(b"PROPFIND").unwrap();
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/files/*").method(propfind, async || "properties");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource already has an endpoint without guards for `method`.
     pub fn method<T: Endpoint<Data, U>, U>(
         &mut self,
         method: http::Method,
         ep: T,
     ) -> &mut EndpointData<Data> {
-        let resource = self.table.resource_mut();
-        if resource.is_none() {
-            let new_resource = ResourceData {
-                endpoints: HashMap::new(),
-                middleware: self.middleware_base.clone(),
-            };
-            *resource = Some(new_resource);
+        self.methods(&[method], ep)
+    }
+
+    /// Add a single endpoint for several HTTP `methods`.
+    ///
+    /// The endpoint and its configuration are shared by all `methods`. It can use
+    /// [`Head::method`] to find out which method was used.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// use http::Method;
+    /// use tide::head::Head;
+    ///
+    /// let mkcol = Method::from_bytes(b"MKCOL").unwrap();
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/files/*").methods(&[Method::PUT, mkcol], async move |head: Head| {
+    ///     format!("{} {}", head.method(), head.path())
+    /// });
+    /// ```
+    ///
+    /// Several endpoints can be added for the same method if the earlier ones have
+    /// [guards](guard/index.html); the first endpoint whose guards accept a request handles it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `methods` is empty, or if the resource already has an endpoint without guards
+    /// for one of them.
+    ///
+    /// [`Head::method`]: head/struct.Head.html#method.method
+    pub fn methods<T: Endpoint<Data, U>, U>(
+        &mut self,
+        methods: &[http::Method],
+        ep: T,
+    ) -> &mut EndpointData<Data> {
+        if methods.is_empty() {
+            panic!("An endpoint needs at least one method");
         }
-        let resource = resource.as_mut().unwrap();
 
-        let entry = resource.endpoints.entry(method);
-        if let std::collections::hash_map::Entry::Occupied(ep) = entry {
-            panic!("A {} endpoint already exists for this path", ep.key())
+        let resource = resource_data(self.table, self.path.clone(), self.middleware_base);
+        if let Some(method) = methods.iter().find(|m| !resource.accepts_endpoint(m)) {
+            panic!("A {} endpoint without guards already exists for this path", method)
         }
 
-        let endpoint = EndpointData {
-            endpoint: BoxedEndpoint::new(ep),
-            store: Store::new(),
-        };
+        let idx = resource.endpoints.len();
+        let mut endpoint = EndpointData::new(BoxedEndpoint::new(ep), &resource.middleware);
+        endpoint.segment_names = wildcard_names(&self.path);
+        resource.endpoints.push(endpoint);
+        for method in methods {
+            let entry = resource.methods.entry(method.clone()).or_insert_with(Vec::new);
+            entry.push(idx);
+        }
+        &mut resource.endpoints[idx]
+    }
 
-        entry.or_insert(endpoint)
+    /// Apply `middleware` to all endpoints of this resource, including those added later.
+    ///
+    /// It runs after the middleware of the router and before the middleware of single endpoints,
+    /// regardless of the order in which they were added.
+    pub fn middleware(&mut self, middleware: impl Middleware<Data> + 'static) -> &mut Self {
+        let middleware: Arc<dyn Middleware<Data> + Send + Sync> = Arc::new(middleware);
+        let resource = resource_data(self.table, self.path.clone(), self.middleware_base);
+        resource.push_middleware(&middleware);
+        self
     }
 
     /// Add an endpoint for `GET` requests
@@ -300,6 +1478,9 @@ impl<'a, Data> Resource<'a, Data> {
     }
 
     /// Add an endpoint for `OPTIONS` requests
+    ///
+    /// Without such an endpoint, the router answers `OPTIONS` requests itself, listing the
+    /// methods of the resource in the `Allow` header.
     pub fn options<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
         self.method(http::Method::OPTIONS, ep)
     }
@@ -338,28 +1519,23 @@ mod tests {
         path: &'a str,
         method: &'a http::Method,
     ) -> Option<Response> {
-        let default_handler = Arc::new(EndpointData {
-            endpoint: BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
-            store: Store::new(),
-        });
+        let fallbacks = Fallbacks::new();
+        let req = request(path, method);
         let RouteResult {
             endpoint,
             params,
             middleware,
-        } = router.route(path, method, &default_handler);
+            ..
+        } = router.route(path, &req, &fallbacks);
 
         let data = Data::default();
-        let req = http::Request::builder()
-            .method(method)
-            .body(http_service::Body::empty())
-            .unwrap();
 
         let ctx = RequestContext {
             app_data: data,
             req,
             params,
             endpoint,
-            next_middleware: middleware,
+            next_middleware: &middleware,
         };
         let res = await!(ctx.next());
         Some(res.map(Into::into))
@@ -370,14 +1546,19 @@ mod tests {
         path: &str,
         method: &http::Method,
     ) -> Option<usize> {
-        let default_handler = Arc::new(EndpointData {
-            endpoint: BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
-            store: Store::new(),
-        });
-        let route_result = router.route(path, method, &default_handler);
+        let fallbacks = Fallbacks::new();
+        let route_result = router.route(path, &request(path, method), &fallbacks);
         Some(route_result.middleware.len())
     }
 
+    fn request(path: &str, method: &http::Method) -> Request {
+        http::Request::builder()
+            .method(method)
+            .uri(path)
+            .body(http_service::Body::empty())
+            .unwrap()
+    }
+
     #[test]
     fn simple_static() {
         let mut router: Router<()> = Router::new();
@@ -466,6 +1647,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn method_not_allowed() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "/a GET");
+        router.at("/a").post(async || "/a POST");
+
+        let res = block_on(simulate_request(&router, "/a", &http::Method::DELETE)).unwrap();
+        assert_eq!(res.status(), 405);
+
+        let res = block_on(simulate_request(&router, "/b", &http::Method::DELETE)).unwrap();
+        assert_eq!(res.status(), 404);
+
+        let fallbacks = Fallbacks::new();
+        let allowed = router
+            .route("/a", &request("/a", &http::Method::DELETE), &fallbacks)
+            .allowed
+            .expect("Mismatched method should report the allowed methods");
+        assert_eq!(
+            allowed.0,
+            vec![
+                http::Method::GET,
+                http::Method::HEAD,
+                http::Method::OPTIONS,
+                http::Method::POST
+            ]
+        );
+        assert_eq!(allowed.header_value(), "GET, HEAD, OPTIONS, POST");
+    }
+
+    #[test]
+    fn automatic_options() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "/a GET");
+        router.at("/b").get(async || "/b GET");
+        router.at("/b").options(async || "/b OPTIONS");
+
+        let fallbacks = Fallbacks::new();
+        let route_result = router.route("/a", &request("/a", &http::Method::OPTIONS), &fallbacks);
+        let allowed = route_result
+            .allowed
+            .expect("Automatic OPTIONS should report the allowed methods");
+        assert_eq!(allowed.header_value(), "GET, HEAD, OPTIONS");
+
+        let res = block_on(simulate_request(&router, "/a", &http::Method::OPTIONS)).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let res = block_on(simulate_request(&router, "/b", &http::Method::OPTIONS)).unwrap();
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, &*b"/b OPTIONS");
+
+        let res = block_on(simulate_request(&router, "/c", &http::Method::OPTIONS)).unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
     #[test]
     #[should_panic]
     fn duplicate_endpoint_fails() {
@@ -551,6 +1786,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn endpoint_middleware() {
+        #[derive(Default, Clone, Debug)]
+        struct Data(Vec<usize>);
+        struct Pusher(usize);
+        impl Middleware<Data> for Pusher {
+            fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+                FutureObj::new(Box::new(
+                    async move {
+                        ctx.app_data.0.push(self.0);
+                        await!(ctx.next())
+                    },
+                ))
+            }
+        }
+
+        async fn check(data: AppData<Data>) -> String {
+            format!("{:?}", (data.0).0)
+        }
+
+        // Router middleware runs first, then resource middleware, then endpoint middleware
+        let mut router: Router<Data> = Router::new();
+        router.at("/").get(check);
+        router.at("/").post(check).middleware(Pusher(2));
+        router.at("/").middleware(Pusher(1));
+        router.at("/").put(check);
+        router.middleware(Pusher(0));
+
+        for (method, expected) in &[
+            (http::Method::GET, "[0, 1]"),
+            (http::Method::POST, "[0, 1, 2]"),
+            (http::Method::PUT, "[0, 1]"),
+        ] {
+            let res = block_on(simulate_request(&router, "/", method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+        assert_eq!(route_middleware_count(&router, "/", &http::Method::POST), Some(3));
+    }
+
     #[test]
     fn configuration() {
         use crate::ExtractConfiguration;
@@ -607,6 +1882,308 @@ mod tests {
         assert_eq!(&*body, &*b"baz");
     }
 
+    #[test]
+    fn named_routes() {
+        let mut router: Router<()> = Router::new();
+        router.at("/").name("root").get(async || "/");
+        router.at("/a/{id}").nest(|router| {
+            router.at("/").name("a").get(async || "/a");
+            router.at("/b/*").name("b").get(async || "/a/b");
+        });
+
+        let urls = router.urls();
+        assert_eq!(urls.url_for("root", &[]).unwrap(), "/");
+        assert_eq!(urls.url_for("a", &["1"]).unwrap(), "/a/1");
+        assert_eq!(urls.url_for("b", &["1", "c/d"]).unwrap(), "/a/1/b/c/d");
+    }
+
+    #[test]
+    fn constrained_segments() {
+        use crate::head::Path;
+
+        let mut router: Router<()> = Router::new();
+        router.matcher("upper", |s: &str| s.chars().all(|c| c.is_ascii_uppercase()));
+        router.at("/users/me").get(async || "me".to_string());
+        router
+            .at("/users/{id:u64}")
+            .get(async move |Path(id): Path<u64>| format!("id {}", id));
+        router.at("/users/{name}").nest(|router| {
+            router.at("/{code:upper}").get(
+                async move |Path(name): Path<String>, Path(code): Path<String>| {
+                    format!("{} {}", name, code)
+                },
+            );
+        });
+
+        for (path, expected) in &[
+            ("/users/me", "me"),
+            ("/users/42", "id 42"),
+            ("/users/jane/ABC", "jane ABC"),
+            ("/users/-1/ABC", "-1 ABC"),
+        ] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+
+        for path in &["/users/jane", "/users/jane/abc", "/users/42/abc"] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            assert_eq!(res.status(), 404);
+        }
+    }
+
+    #[test]
+    fn routes() {
+        let mut router: Router<()> = Router::new();
+        router.config("foo");
+        router.at("/b").nest(|router| {
+            router.middleware(passthrough_middleware);
+            router.at("/{id:u64}").get(async || "");
+            router
+                .at("/{id:u64}")
+                .post(async || "")
+                .config("bar")
+                .middleware(passthrough_middleware);
+        });
+        router.at("/a").get(async || "");
+
+        let routes: Vec<_> = router.routes().collect();
+        assert_eq!(routes.len(), 2);
+
+        assert_eq!(routes[0].path(), "/a");
+        assert_eq!(routes[0].methods(), vec![&http::Method::GET]);
+        assert_eq!(routes[0].middleware_count(&http::Method::GET), Some(0));
+        assert_eq!(routes[0].middleware_count(&http::Method::POST), None);
+        let store = routes[0].store(&http::Method::GET).unwrap();
+        assert_eq!(store.read::<&'static str>(), Some(&"foo"));
+        assert!(routes[0].store(&http::Method::POST).is_none());
+
+        assert_eq!(routes[1].path(), "/b/{id:u64}");
+        assert_eq!(
+            routes[1].methods(),
+            vec![&http::Method::GET, &http::Method::POST]
+        );
+        assert_eq!(routes[1].middleware_count(&http::Method::GET), Some(1));
+        assert_eq!(routes[1].middleware_count(&http::Method::POST), Some(2));
+        let store = routes[1].store(&http::Method::POST).unwrap();
+        assert_eq!(store.read::<&'static str>(), Some(&"bar"));
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "/a");
+        router.at("/b/").get(async || "/b/");
+        router.at("/strict").nest(|router| {
+            router.config(TrailingSlash::Strict);
+            router.at("/a").get(async || "/strict/a");
+            router.at("/b/").get(async || "/strict/b/");
+        });
+        router.at("/redirect").nest(|router| {
+            router.config(TrailingSlash::Redirect(http::StatusCode::PERMANENT_REDIRECT));
+            router.at("/a").get(async || "/redirect/a");
+        });
+        router.at("/api").nest(|router| {
+            router.config(TrailingSlash::Strict);
+            router.at("/users").get(async || "/api/users");
+            router.default_handler(async || "api default");
+        });
+        router.apply_default_config(); // simulating App behavior
+
+        for (path, expected) in &[
+            ("/a", "/a"),
+            ("/a/", "/a"),
+            ("/b", "/b/"),
+            ("/b/", "/b/"),
+            ("/strict/a", "/strict/a"),
+            ("/strict/b/", "/strict/b/"),
+            ("/redirect/a", "/redirect/a"),
+            ("/api/users", "/api/users"),
+            ("/api/users/", "api default"),
+        ] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+
+        for path in &["/strict/a/", "/strict/b"] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            assert_eq!(res.status(), 404);
+        }
+
+        let fallbacks = Fallbacks::new();
+        let req = request("/redirect/a/", &http::Method::GET);
+        let route_result = router.route("/redirect/a/", &req, &fallbacks);
+        assert_eq!(
+            route_result.redirect.map(|redirect| redirect.0),
+            Some(http::StatusCode::PERMANENT_REDIRECT)
+        );
+    }
+
+    #[test]
+    fn subtree_default_handler() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "/a");
+        router.at("/api").nest(|router| {
+            router.at("/items/{id:u64}").get(async || "/api/items");
+            router
+                .at("/beta")
+                .get(async || "/api/beta")
+                .guard(|req: &Request| req.headers().contains_key("x-beta"));
+            router.default_handler(async || "api default");
+            router.at("/v2").nest(|router| {
+                router.middleware(passthrough_middleware);
+                router.default_handler(async || "v2 default");
+            });
+        });
+
+        for (path, expected) in &[
+            ("/api", "api default"),
+            ("/api/items/1", "/api/items"),
+            ("/api/items/x", "api default"),
+            ("/api/beta", "api default"),
+            ("/api/v2", "v2 default"),
+            ("/api/v2/items/1", "v2 default"),
+        ] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+
+        let res = block_on(simulate_request(&router, "/apix", &http::Method::GET)).unwrap();
+        assert_eq!(res.status(), 404);
+        assert_eq!(
+            route_middleware_count(&router, "/api/v2/x", &http::Method::GET),
+            Some(1)
+        );
+        assert_eq!(router.routes().count(), 3);
+    }
+
+    #[test]
+    fn merge_routers() {
+        let mut users: Router<()> = Router::new();
+        users.origin("users");
+        users.at("/").name("users").get(async || "users");
+        users.at("/{id:u64}").get(async || "user");
+        users.middleware(passthrough_middleware);
+
+        let mut admin: Router<()> = Router::new();
+        admin.at("/").post(async || "created");
+
+        let mut router: Router<()> = Router::new();
+        router.middleware(passthrough_middleware);
+        router.at("/").get(async || "/");
+        router.at("/users").merge(users).unwrap();
+        router.at("/users/").merge(admin).unwrap();
+
+        for (method, path, expected) in &[
+            (http::Method::GET, "/", "/"),
+            (http::Method::GET, "/users", "users"),
+            (http::Method::POST, "/users", "created"),
+            (http::Method::GET, "/users/3", "user"),
+        ] {
+            let res = block_on(simulate_request(&router, path, method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+        assert_eq!(
+            route_middleware_count(&router, "/users/3", &http::Method::GET),
+            Some(2)
+        );
+        assert_eq!(
+            route_middleware_count(&router, "/users", &http::Method::POST),
+            Some(1)
+        );
+        assert_eq!(router.urls().template("users"), Some("/users"));
+    }
+
+    #[test]
+    fn merge_conflict() {
+        use crate::head::{Named, NamedSegment};
+
+        let mut users: Router<()> = Router::new();
+        users.origin("users");
+        users.at("/").get(async || "users");
+        users.at("/{id}").get(async || "user");
+
+        let mut admin: Router<()> = Router::new();
+        admin.origin("admin");
+        admin.at("/{id}/").get(async || "admin");
+
+        let mut router: Router<()> = Router::new();
+        router.at("/users").merge(users).unwrap();
+        let conflict = router.at("/users").merge(admin).unwrap_err();
+        assert_eq!(conflict.path(), "/users/{id}/");
+        assert_eq!(conflict.endpoint(), "GET");
+        assert_eq!(conflict.existing_origin(), Some("users"));
+        assert_eq!(conflict.merged_origin(), Some("admin"));
+        assert_eq!(
+            conflict.to_string(),
+            "conflicting GET for `/users/{id}/`, defined by `users` and `admin`"
+        );
+
+        let mut other: Router<()> = Router::new();
+        other.at("/users").get(async || "other");
+        let conflict = router.at("/").merge(other).unwrap_err();
+        assert_eq!(conflict.existing_origin(), Some("users"));
+        assert_eq!(conflict.merged_origin(), None);
+
+        // Wildcard names do not distinguish routes, constraints do.
+        let mut renamed: Router<()> = Router::new();
+        renamed.origin("renamed");
+        renamed.at("/{user_id}").get(async || "renamed");
+        let conflict = router.at("/users").merge(renamed).unwrap_err();
+        assert_eq!(conflict.path(), "/users/{user_id}");
+        assert_eq!(conflict.existing_origin(), Some("users"));
+        assert_eq!(conflict.merged_origin(), Some("renamed"));
+
+        let mut constrained: Router<()> = Router::new();
+        constrained.at("/{id:u64}").get(async || "constrained");
+        router.at("/users").merge(constrained).unwrap();
+
+        // Other methods under a different wildcard name join the existing resource.
+        struct UserId(String);
+
+        impl NamedSegment for UserId {
+            const NAME: &'static str = "user_id";
+        }
+
+        impl std::str::FromStr for UserId {
+            type Err = std::string::ParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(UserId(s.to_owned()))
+            }
+        }
+
+        let mut posts: Router<()> = Router::new();
+        posts
+            .at("/{user_id}")
+            .post(async move |Named(UserId(id)): Named<UserId>| format!("posted {}", id));
+        router.at("/users").merge(posts).unwrap();
+
+        for (method, path, expected) in &[
+            (http::Method::GET, "/users/me", "user"),
+            (http::Method::GET, "/users/7", "constrained"),
+            (http::Method::POST, "/users/me", "posted me"),
+        ] {
+            let res = block_on(simulate_request(&router, path, method)).unwrap();
+            assert_eq!(res.status(), 200);
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_name_fails() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").name("a").get(async || "/a");
+        router.at("/b").nest(|router| {
+            router.at("/").name("a").get(async || "/b");
+        });
+    }
+
     #[test]
     fn configuration_order() {
         use crate::ExtractConfiguration;
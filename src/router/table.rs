@@ -0,0 +1,543 @@
+//! The routing table backing `Router`.
+//!
+//! This works like the table of the `path_table` crate, but a node may hold several wildcard
+//! segments, each optionally restricted by a constraint (`{id:u64}`, `{slug:[a-z-]+}`). When a
+//! branch does not lead to a resource, routing backtracks and tries the next candidate, so a
+//! constrained segment that does not match falls through to other routes.
+//!
+//! Branches are keyed by the kind and constraint of their wildcard, not by its name, so
+//! `/users/{id}` and `/users/{user_id}` lead to the same resource. The table only reports the
+//! matched segments in order; naming them is up to the resources.
+//!
+//! Constraints see the percent-decoded segment, like the `head::Path` and `head::Named`
+//! extractors, so `{id:u64}` matches `%31`. A segment that is not valid UTF-8 after decoding
+//! matches no constraint.
+
+use percent_encoding::percent_decode;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A predicate deciding whether a constrained wildcard segment matches.
+pub(crate) type Matcher = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Named matchers available to route templates, in addition to the built-in ones.
+pub(crate) type Matchers = HashMap<String, Matcher>;
+
+/// A routing table, terminating with resources `R`.
+#[derive(Clone)]
+pub(crate) struct PathTable<R> {
+    accept: Option<R>,
+    next: HashMap<String, PathTable<R>>,
+    /// Wildcard branches, ordered by the priority in which they are tried.
+    wildcards: Vec<Wildcard<R>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum WildcardKind {
+    Segment,
+    CatchAll,
+}
+
+#[derive(Clone)]
+struct Wildcard<R> {
+    constraint: Option<Constraint>,
+    kind: WildcardKind,
+    table: PathTable<R>,
+}
+
+impl<R> Wildcard<R> {
+    /// Branches are tried in this order: constrained segments, unconstrained segments, and
+    /// finally catch-all wildcards.
+    fn priority(&self) -> (WildcardKind, bool) {
+        (self.kind, self.constraint.is_none())
+    }
+
+    fn accepts(&self, segment: &str) -> bool {
+        let constraint = match &self.constraint {
+            Some(constraint) => constraint,
+            None => return true,
+        };
+        match percent_decode(segment.as_bytes()).decode_utf8() {
+            Ok(segment) => (constraint.matcher)(&segment),
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Constraint {
+    source: String,
+    matcher: Matcher,
+}
+
+impl Constraint {
+    /// Parse the constraint of a wildcard segment.
+    ///
+    /// A constraint that is a plain identifier names a matcher, either one of the built-in types
+    /// or one of `matchers`. Anything else is a regular expression that must match the whole
+    /// segment.
+    fn parse(source: &str, matchers: &Matchers) -> Constraint {
+        let is_ident = source
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+
+        let matcher = if is_ident {
+            match matchers.get(source) {
+                Some(matcher) => matcher.clone(),
+                None => builtin_matcher(source)
+                    .unwrap_or_else(|| panic!("Unknown segment matcher `{}`", source)),
+            }
+        } else {
+            let regex = Regex::new(&format!("^(?:{})$", source))
+                .unwrap_or_else(|err| panic!("Invalid segment pattern `{}`: {}", source, err));
+            Arc::new(move |segment: &str| regex.is_match(segment)) as Matcher
+        };
+
+        Constraint {
+            source: source.to_owned(),
+            matcher,
+        }
+    }
+}
+
+/// The matcher of the wildcard constraint `source`, as used for routing.
+pub(crate) fn constraint_matcher(source: &str, matchers: &Matchers) -> Matcher {
+    Constraint::parse(source, matchers).matcher
+}
+
+fn builtin_matcher(name: &str) -> Option<Matcher> {
+    fn parses<T: std::str::FromStr>(segment: &str) -> bool {
+        segment.parse::<T>().is_ok()
+    }
+
+    let matcher: fn(&str) -> bool = match name {
+        "u8" => parses::<u8>,
+        "u16" => parses::<u16>,
+        "u32" => parses::<u32>,
+        "u64" => parses::<u64>,
+        "usize" => parses::<usize>,
+        "i8" => parses::<i8>,
+        "i16" => parses::<i16>,
+        "i32" => parses::<i32>,
+        "i64" => parses::<i64>,
+        "isize" => parses::<isize>,
+        "f32" => parses::<f32>,
+        "f64" => parses::<f64>,
+        "bool" => parses::<bool>,
+        _ => return None,
+    };
+    Some(Arc::new(matcher))
+}
+
+/// Split a path template into its non-empty segments.
+///
+/// Slashes within the braces of a wildcard definition, e.g. in the constraint of `{p:a/b}*`, do
+/// not separate segments.
+pub(crate) fn template_segments(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, c) in path.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '/' if depth == 0 => {
+                segments.push(&path[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&path[start..]);
+    segments.retain(|segment| !segment.is_empty());
+    segments
+}
+
+/// Split a wildcard definition such as `{id:u64}*` into its name, constraint and kind.
+///
+/// Returns `None` for concrete segments.
+pub(crate) fn parse_wildcard(segment: &str) -> Option<(&str, Option<&str>, bool)> {
+    let (inner, catch_all) = if segment == "*" {
+        ("", true)
+    } else if segment.starts_with('{') && segment.ends_with("}*") {
+        (&segment[1..segment.len() - 2], true)
+    } else if segment.starts_with('{') && segment.ends_with('}') {
+        (&segment[1..segment.len() - 1], false)
+    } else {
+        return None;
+    };
+
+    match inner.find(':') {
+        Some(idx) => Some((&inner[..idx], Some(&inner[idx + 1..]), catch_all)),
+        None => Some((inner, None, catch_all)),
+    }
+}
+
+/// The names of the wildcard segments of a path template, in order. Unnamed wildcards have an
+/// empty name.
+pub(crate) fn wildcard_names(path: &str) -> Vec<String> {
+    template_segments(path)
+        .into_iter()
+        .filter_map(parse_wildcard)
+        .map(|(name, _, _)| name.to_owned())
+        .collect()
+}
+
+impl<R> Default for PathTable<R> {
+    fn default() -> Self {
+        PathTable::new()
+    }
+}
+
+impl<R> PathTable<R> {
+    /// Create an empty routing table.
+    pub(crate) fn new() -> PathTable<R> {
+        PathTable {
+            accept: None,
+            next: HashMap::new(),
+            wildcards: Vec::new(),
+        }
+    }
+
+    /// Retrieve a mutable reference of the resource.
+    pub(crate) fn resource_mut(&mut self) -> &mut Option<R> {
+        &mut self.accept
+    }
+
+    /// Return an iterator of all resources.
+    pub(crate) fn iter(&self) -> Resources<'_, R> {
+        Resources { stack: vec![self] }
+    }
+
+    /// Return a mutable iterator of all resources.
+    pub(crate) fn iter_mut(&mut self) -> ResourcesMut<'_, R> {
+        ResourcesMut { stack: vec![self] }
+    }
+
+    /// Consume the table, returning all of its resources.
+    pub(crate) fn into_resources(self) -> Vec<R> {
+        let mut resources = Vec::new();
+        let mut stack = vec![self];
+        while let Some(table) = stack.pop() {
+            stack.extend(table.next.into_iter().map(|(_, table)| table));
+            stack.extend(table.wildcards.into_iter().map(|wildcard| wildcard.table));
+            resources.extend(table.accept);
+        }
+        resources
+    }
+
+    /// Determine which resource, if any, the concrete `path` should be routed to, together with
+    /// the segments matched by its wildcards.
+    pub(crate) fn route<'a>(&'a self, path: &'a str) -> Option<(&'a R, Vec<&'a str>)> {
+        self.route_filtered(path, |_| true)
+    }
+
+    /// Like `route`, but skip resources for which `accept` returns `false`, backtracking to the
+    /// next candidate as for a constraint that does not match.
+    pub(crate) fn route_filtered<'a>(
+        &'a self,
+        path: &'a str,
+        accept: impl Fn(&R) -> bool,
+    ) -> Option<(&'a R, Vec<&'a str>)> {
+        // Find all non-empty segments with their indices.
+        let segments: Vec<(usize, &str)> = path
+            .match_indices('/')
+            .chain(std::iter::once((path.len(), "")))
+            .scan(0usize, |prev_idx, (idx, _)| {
+                let starts_at = *prev_idx;
+                let segment = &path[starts_at..idx];
+                *prev_idx = idx + 1;
+                Some((starts_at, segment))
+            })
+            .filter(|(_, segment)| !segment.is_empty())
+            .collect();
+
+        let mut params = Vec::new();
+        let resource = self.route_segments(path, &segments, &mut params, &accept)?;
+        Some((resource, params))
+    }
+
+    fn route_segments<'a>(
+        &'a self,
+        path: &'a str,
+        segments: &[(usize, &'a str)],
+        params: &mut Vec<&'a str>,
+        accept: &impl Fn(&R) -> bool,
+    ) -> Option<&'a R> {
+        let (starts_at, segment, rest) = match segments.split_first() {
+            Some((&(starts_at, segment), rest)) => (starts_at, segment, rest),
+            None => {
+                if let Some(resource) = self.accepted(accept) {
+                    return Some(resource);
+                }
+                // A catch-all wildcard also matches an empty remainder.
+                let wildcard = self.wildcards.iter().find(|wildcard| {
+                    wildcard.kind == WildcardKind::CatchAll
+                        && wildcard.table.accepted(accept).is_some()
+                        && wildcard.accepts("")
+                })?;
+                params.push("");
+                return wildcard.table.accepted(accept);
+            }
+        };
+
+        if let Some(next_table) = self.next.get(segment) {
+            if let Some(resource) = next_table.route_segments(path, rest, params, accept) {
+                return Some(resource);
+            }
+        }
+
+        for wildcard in &self.wildcards {
+            match wildcard.kind {
+                WildcardKind::Segment => {
+                    if !wildcard.accepts(segment) {
+                        continue;
+                    }
+                    params.push(segment);
+                    let table = &wildcard.table;
+                    if let Some(resource) = table.route_segments(path, rest, params, accept) {
+                        return Some(resource);
+                    }
+                    params.pop();
+                }
+                WildcardKind::CatchAll => {
+                    let remainder = &path[starts_at..];
+                    let resource = wildcard.table.accepted(accept);
+                    if resource.is_some() && wildcard.accepts(remainder) {
+                        params.push(remainder);
+                        return resource;
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn accepted(&self, accept: &impl Fn(&R) -> bool) -> Option<&R> {
+        self.accept.as_ref().filter(|resource| accept(resource))
+    }
+
+    /// Return the table of the given routing path (which may contain wildcards).
+    ///
+    /// If it doesn't already exist, this will make a new one. Wildcards which only differ in their
+    /// name share a table. Named constraints are looked up in `matchers` before the built-in ones.
+    pub(crate) fn setup_table(&mut self, path: &str, matchers: &Matchers) -> &mut PathTable<R> {
+        let mut table = self;
+        let mut forbid_next = false;
+        for segment in template_segments(path) {
+            if forbid_next {
+                panic!("No segments are allowed after wildcard with `*` modifier");
+            }
+
+            table = match parse_wildcard(segment) {
+                Some((_, constraint, catch_all)) => {
+                    forbid_next = catch_all;
+                    let kind = if catch_all {
+                        WildcardKind::CatchAll
+                    } else {
+                        WildcardKind::Segment
+                    };
+                    table.wildcard_mut(constraint, kind, matchers)
+                }
+                None => table
+                    .next
+                    .entry(segment.to_string())
+                    .or_insert_with(PathTable::new),
+            };
+        }
+
+        table
+    }
+
+    fn wildcard_mut(
+        &mut self,
+        constraint: Option<&str>,
+        kind: WildcardKind,
+        matchers: &Matchers,
+    ) -> &mut PathTable<R> {
+        let existing = self.wildcards.iter().position(|wildcard| {
+            wildcard.kind == kind && wildcard.constraint.as_ref().map(|c| &*c.source) == constraint
+        });
+
+        let idx = match existing {
+            Some(idx) => idx,
+            None => {
+                let wildcard = Wildcard {
+                    constraint: constraint.map(|source| Constraint::parse(source, matchers)),
+                    kind,
+                    table: PathTable::new(),
+                };
+                // Keep the order of registration among wildcards of the same priority.
+                let idx = self
+                    .wildcards
+                    .iter()
+                    .position(|other| other.priority() > wildcard.priority())
+                    .unwrap_or_else(|| self.wildcards.len());
+                self.wildcards.insert(idx, wildcard);
+                idx
+            }
+        };
+
+        &mut self.wildcards[idx].table
+    }
+}
+
+/// An iterator over the resources of a `PathTable`.
+pub(crate) struct Resources<'a, R> {
+    stack: Vec<&'a PathTable<R>>,
+}
+
+impl<'a, R> Iterator for Resources<'a, R> {
+    type Item = &'a R;
+
+    fn next(&mut self) -> Option<&'a R> {
+        while let Some(table) = self.stack.pop() {
+            self.stack.extend(table.next.values());
+            self.stack
+                .extend(table.wildcards.iter().map(|wildcard| &wildcard.table));
+            if let Some(res) = &table.accept {
+                return Some(res);
+            }
+        }
+        None
+    }
+}
+
+/// A mutable iterator over the resources of a `PathTable`.
+pub(crate) struct ResourcesMut<'a, R> {
+    stack: Vec<&'a mut PathTable<R>>,
+}
+
+impl<'a, R> Iterator for ResourcesMut<'a, R> {
+    type Item = &'a mut R;
+
+    fn next(&mut self) -> Option<&'a mut R> {
+        while let Some(table) = self.stack.pop() {
+            let PathTable {
+                accept,
+                next,
+                wildcards,
+            } = table;
+            self.stack.extend(next.values_mut());
+            self.stack
+                .extend(wildcards.iter_mut().map(|wildcard| &mut wildcard.table));
+            if let Some(res) = accept {
+                return Some(res);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(paths: &[&str]) -> PathTable<String> {
+        let mut table = PathTable::new();
+        for path in paths {
+            *table.setup_table(path, &Matchers::new()).resource_mut() = Some(path.to_string());
+        }
+        table
+    }
+
+    fn route<'a>(table: &'a PathTable<String>, path: &'a str) -> Option<&'a str> {
+        table.route(path).map(|(resource, _)| &**resource)
+    }
+
+    #[test]
+    fn concrete_before_wildcard() {
+        let table = build(&["/users/{id}", "/users/me"]);
+        assert_eq!(route(&table, "/users/me"), Some("/users/me"));
+        assert_eq!(route(&table, "/users/3"), Some("/users/{id}"));
+    }
+
+    #[test]
+    fn constraint_falls_through() {
+        let table = build(&["/users/{id:u64}", "/users/{slug:[a-z-]+}", "/users/*"]);
+        assert_eq!(route(&table, "/users/42"), Some("/users/{id:u64}"));
+        assert_eq!(route(&table, "/users/jane-doe"), Some("/users/{slug:[a-z-]+}"));
+        assert_eq!(route(&table, "/users/Jane"), Some("/users/*"));
+
+        let table = build(&["/users/{id:u64}"]);
+        assert_eq!(route(&table, "/users/me"), None);
+    }
+
+    #[test]
+    fn backtracking() {
+        let table = build(&["/users/me/settings", "/users/{id}/posts"]);
+        assert_eq!(route(&table, "/users/me/posts"), Some("/users/{id}/posts"));
+        assert_eq!(route(&table, "/users/me/settings"), Some("/users/me/settings"));
+    }
+
+    #[test]
+    fn filtered() {
+        let table = build(&["/a/*", "/a/b/*", "/a/b/c"]);
+        let route = |path| {
+            table
+                .route_filtered(path, |resource| resource.ends_with('*'))
+                .map(|(resource, _)| &**resource)
+        };
+        assert_eq!(route("/a/b/c"), Some("/a/b/*"));
+        assert_eq!(route("/a/c"), Some("/a/*"));
+        assert_eq!(route("/b"), None);
+    }
+
+    #[test]
+    fn params() {
+        let table = build(&["/a/{x:u8}/{}/{rest}*"]);
+        let (_, params) = table.route("/a/1/b/c/d").unwrap();
+        assert_eq!(params, vec!["1", "b", "c/d"]);
+        assert_eq!(wildcard_names("/a/{x:u8}/{}/{rest}*"), vec!["x", "", "rest"]);
+    }
+
+    #[test]
+    fn wildcard_names_share_branch() {
+        let mut table = PathTable::new();
+        *table.setup_table("/users/{id}", &Matchers::new()).resource_mut() = Some(1);
+        let resource = table.setup_table("/users/{uid}", &Matchers::new()).resource_mut();
+        assert_eq!(*resource, Some(1));
+        assert_eq!(table.wildcards.len(), 0);
+        assert_eq!(table.next["users"].wildcards.len(), 1);
+    }
+
+    #[test]
+    fn custom_matcher() {
+        let mut matchers = Matchers::new();
+        matchers.insert("even".to_owned(), Arc::new(|s: &str| s.len() % 2 == 0));
+        let mut table = PathTable::new();
+        *table.setup_table("/{x:even}", &matchers).resource_mut() = Some(());
+        assert!(table.route("/ab").is_some());
+        assert!(table.route("/abc").is_none());
+    }
+
+    #[test]
+    fn slash_in_constraint() {
+        assert_eq!(template_segments("/a/{p:b/c}*/"), vec!["a", "{p:b/c}*"]);
+        assert_eq!(template_segments("//{x:[/]}/y"), vec!["{x:[/]}", "y"]);
+
+        let table = build(&["/files/{p:docs/[a-z]+}*"]);
+        assert_eq!(route(&table, "/files/docs/intro"), Some("/files/{p:docs/[a-z]+}*"));
+        assert_eq!(route(&table, "/files/img/logo"), None);
+    }
+
+    #[test]
+    fn constraint_sees_decoded_segment() {
+        let table = build(&["/users/{id:u64}", "/tags/{tag:[a-z ]+}"]);
+        assert_eq!(route(&table, "/users/%31%32"), Some("/users/{id:u64}"));
+        assert_eq!(route(&table, "/tags/rust%20lang"), Some("/tags/{tag:[a-z ]+}"));
+        assert_eq!(route(&table, "/users/%FF"), None);
+
+        let (_, params) = table.route("/users/%31").unwrap();
+        assert_eq!(params, vec!["%31"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unknown_matcher() {
+        build(&["/{x:unknown}"]);
+    }
+}
@@ -0,0 +1,372 @@
+use futures::future;
+use percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{
+    configuration::Store,
+    router::{constraint_matcher, parse_wildcard, template_segments, Matcher, Matchers},
+    Extract, IntoResponse, Rejection, RejectionKind, Request, Response, RouteMatch,
+};
+
+/// The table of named routes of an app, used to generate URLs.
+///
+/// A route is given a name with `Resource::name`. The URL of a named route can then be generated
+/// from the name and values for its wildcard segments, so that links do not break when the path of
+/// the route changes. Endpoints can access the table by using `Urls` as an extractor.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::Urls;
+///
+/// async fn create_message(urls: Urls) -> http::Response<String> {
+///     let location = urls.url_for("message", &["42"]).unwrap();
+///     http::Response::builder()
+///         .status(http::status::StatusCode::CREATED)
+///         .header("Location", location.as_str())
+///         .body(location)
+///         .unwrap()
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/messages").post(create_message);
+///     app.at("/messages/{id}").name("message").get(async || "message");
+///     app.serve()
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Urls {
+    routes: Arc<HashMap<String, Route>>,
+}
+
+/// A named route, with the matchers of the constraints of its wildcard segments in order.
+#[derive(Clone)]
+struct Route {
+    template: String,
+    constraints: Vec<Option<Matcher>>,
+}
+
+impl Route {
+    /// Parse the constraints of `template`, looking up named ones in `matchers`.
+    fn new(template: &str, matchers: &Matchers) -> Route {
+        let constraints = template_segments(template)
+            .into_iter()
+            .filter_map(parse_wildcard)
+            .map(|(_, constraint, _)| constraint.map(|source| constraint_matcher(source, matchers)))
+            .collect();
+        Route {
+            template: template.to_owned(),
+            constraints,
+        }
+    }
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.template, f)
+    }
+}
+
+/// An error generating the URL of a named route.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlError {
+    /// No route has been given this name.
+    UnknownRoute(String),
+    /// No value was given for the named wildcard segment.
+    MissingParam(String),
+    /// A value was given that does not belong to any wildcard segment of the route.
+    ExtraParam(String),
+    /// The value (second) does not match the constraint of the wildcard segment (first), so the
+    /// URL would not lead to the route.
+    InvalidParam(String, String),
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UrlError::UnknownRoute(name) => write!(f, "no route is named `{}`", name),
+            UrlError::MissingParam(segment) => write!(f, "no value for segment `{}`", segment),
+            UrlError::ExtraParam(param) => write!(f, "unexpected parameter `{}`", param),
+            UrlError::InvalidParam(segment, value) => {
+                write!(f, "value `{}` does not match segment `{}`", value, segment)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+impl Urls {
+    pub(crate) fn new() -> Self {
+        Urls::default()
+    }
+
+    /// Register the route `template` under `name`, with named constraints looked up in
+    /// `matchers`.
+    pub(crate) fn insert(&mut self, name: &str, template: &str, matchers: &Matchers) {
+        self.insert_route(name, Route::new(template, matchers));
+    }
+
+    fn insert_route(&mut self, name: &str, route: Route) {
+        let routes = Arc::make_mut(&mut self.routes);
+        if routes.contains_key(name) {
+            panic!("A route named `{}` already exists", name);
+        }
+        routes.insert(name.to_owned(), route);
+    }
+
+    /// Add all routes of `other` to this table.
+    pub(crate) fn extend(&mut self, other: Urls) {
+        for (name, route) in other.routes.iter() {
+            self.insert_route(name, route.clone());
+        }
+    }
+
+    /// Add all routes of `other` to this table, prepending `prefix` to their templates. Named
+    /// constraints of `prefix` are looked up in `matchers`.
+    pub(crate) fn extend_prefixed(&mut self, other: Urls, prefix: &str, matchers: &Matchers) {
+        let prefix = Route::new(prefix, matchers);
+        for (name, route) in other.routes.iter() {
+            let route = Route {
+                template: join_path(&prefix.template, &route.template),
+                constraints: prefix
+                    .constraints
+                    .iter()
+                    .chain(&route.constraints)
+                    .cloned()
+                    .collect(),
+            };
+            self.insert_route(name, route);
+        }
+    }
+
+    /// The names of all routes in this table.
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.routes.keys().map(String::as_str)
+    }
+
+    /// The path template of the route with the given name.
+    pub fn template(&self, name: &str) -> Option<&str> {
+        self.routes.get(name).map(|route| &*route.template)
+    }
+
+    /// Generate the URL of the route `name`, filling its wildcard segments with `params` in order.
+    ///
+    /// Each value is percent-encoded. The value of a `*` segment may contain `/` to span multiple
+    /// segments. Values have to match the constraints of their segments, as they do for routing.
+    pub fn url_for(&self, name: &str, params: &[&str]) -> Result<String, UrlError> {
+        let mut params = params.iter();
+        let url = self.generate(name, |segment| {
+            params
+                .next()
+                .cloned()
+                .ok_or_else(|| UrlError::MissingParam(segment.to_owned()))
+        })?;
+        match params.next() {
+            Some(extra) => Err(UrlError::ExtraParam((*extra).to_owned())),
+            None => Ok(url),
+        }
+    }
+
+    /// Generate the URL of the route `name`, filling its named wildcard segments from `params`.
+    ///
+    /// Fails if the route contains unnamed wildcard segments, or if `params` contains names that
+    /// do not appear in the route.
+    pub fn url_for_named(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let mut used = Vec::new();
+        let url = self.generate(name, |segment| {
+            let key = parse_wildcard(segment).map_or("", |(key, _, _)| key);
+            match params.iter().find(|(param, _)| !key.is_empty() && *param == key) {
+                Some((param, value)) => {
+                    used.push(*param);
+                    Ok(*value)
+                }
+                None => Err(UrlError::MissingParam(segment.to_owned())),
+            }
+        })?;
+        match params.iter().find(|(param, _)| !used.contains(param)) {
+            Some((extra, _)) => Err(UrlError::ExtraParam((*extra).to_owned())),
+            None => Ok(url),
+        }
+    }
+
+    fn generate<'p>(
+        &self,
+        name: &str,
+        mut param: impl FnMut(&str) -> Result<&'p str, UrlError>,
+    ) -> Result<String, UrlError> {
+        let route = self
+            .routes
+            .get(name)
+            .ok_or_else(|| UrlError::UnknownRoute(name.to_owned()))?;
+        let template = &*route.template;
+        let mut constraints = route.constraints.iter();
+        let mut check = |segment: &str, value: &str| match constraints.next() {
+            Some(Some(matcher)) if !matcher(value) => Err(UrlError::InvalidParam(
+                segment.to_owned(),
+                value.to_owned(),
+            )),
+            _ => Ok(()),
+        };
+
+        let mut url = String::new();
+        for segment in template_segments(template) {
+            match parse_wildcard(segment) {
+                Some((_, _, true)) => {
+                    let value = param(segment)?;
+                    let parts: Vec<_> = value.split('/').filter(|s| !s.is_empty()).collect();
+                    // Empty segments are left out of the URL, so the router never sees them.
+                    check(segment, &parts.join("/"))?;
+                    for part in parts {
+                        url.push('/');
+                        url.extend(utf8_percent_encode(part, PATH_SEGMENT_ENCODE_SET));
+                    }
+                }
+                Some((_, _, false)) => {
+                    let value = param(segment)?;
+                    check(segment, value)?;
+                    url.push('/');
+                    url.extend(utf8_percent_encode(value, PATH_SEGMENT_ENCODE_SET));
+                }
+                None => {
+                    url.push('/');
+                    url.push_str(segment);
+                }
+            }
+        }
+
+        if url.is_empty() || template.ends_with('/') {
+            url.push('/');
+        }
+        Ok(url)
+    }
+}
+
+/// Join a router `prefix` and a `path` relative to it into a single path template.
+///
+/// A trailing slash of `path` is kept, unless `path` is the root of the router.
+pub(crate) fn join_path(prefix: &str, path: &str) -> String {
+    let mut segments = template_segments(prefix);
+    segments.extend(template_segments(path));
+    let mut joined = format!("/{}", segments.join("/"));
+    if path.ends_with('/') && !template_segments(path).is_empty() {
+        joined.push('/');
+    }
+    joined
+}
+
+impl<S: 'static> Extract<S> for Urls {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<Urls>() {
+            Some(urls) => future::ok(urls.clone()),
+            None => future::err(
+                Rejection::new(
+                    RejectionKind::Internal,
+                    "Urls",
+                    "The request was not routed by a tide app",
+                )
+                .into_response(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls() -> Urls {
+        let mut matchers = Matchers::new();
+        matchers.insert("css".to_owned(), Arc::new(|s: &str| s.ends_with(".css")));
+        let mut urls = Urls::new();
+        urls.insert("root", "/", &matchers);
+        urls.insert("message", "/messages/{id:u64}", &matchers);
+        urls.insert("pair", "/pair/{}/{second}", &matchers);
+        urls.insert("static", "/static/{path}*", &matchers);
+        urls.insert("dir", "/dir/{name}/", &matchers);
+        urls.insert("style", "/styles/{path:css}*", &matchers);
+        urls
+    }
+
+    #[test]
+    fn positional() {
+        let urls = urls();
+        assert_eq!(urls.url_for("root", &[]), Ok("/".to_owned()));
+        assert_eq!(urls.url_for("message", &["3"]), Ok("/messages/3".to_owned()));
+        assert_eq!(urls.url_for("pair", &["a", "b"]), Ok("/pair/a/b".to_owned()));
+        assert_eq!(
+            urls.url_for("static", &["css/main.css"]),
+            Ok("/static/css/main.css".to_owned())
+        );
+        assert_eq!(
+            urls.url_for("message", &["a b/c"]),
+            Err(UrlError::InvalidParam("{id:u64}".to_owned(), "a b/c".to_owned()))
+        );
+        assert_eq!(
+            urls.url_for("pair", &["a b/c", "d"]),
+            Ok("/pair/a%20b%2Fc/d".to_owned())
+        );
+        assert_eq!(
+            urls.url_for("style", &["/site//main.css"]),
+            Ok("/styles/site/main.css".to_owned())
+        );
+        assert_eq!(
+            urls.url_for("style", &["main.js"]),
+            Err(UrlError::InvalidParam("{path:css}*".to_owned(), "main.js".to_owned()))
+        );
+        assert_eq!(urls.url_for("dir", &["a"]), Ok("/dir/a/".to_owned()));
+    }
+
+    #[test]
+    fn named() {
+        let urls = urls();
+        assert_eq!(
+            urls.url_for_named("message", &[("id", "3")]),
+            Ok("/messages/3".to_owned())
+        );
+        assert_eq!(
+            urls.url_for_named("pair", &[("second", "b")]),
+            Err(UrlError::MissingParam("{}".to_owned()))
+        );
+        assert_eq!(
+            urls.url_for_named("message", &[("id", "3"), ("page", "2")]),
+            Err(UrlError::ExtraParam("page".to_owned()))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let urls = urls();
+        assert_eq!(
+            urls.url_for("missing", &[]),
+            Err(UrlError::UnknownRoute("missing".to_owned()))
+        );
+        assert_eq!(
+            urls.url_for("message", &[]),
+            Err(UrlError::MissingParam("{id:u64}".to_owned()))
+        );
+        assert_eq!(
+            urls.url_for("message", &["1", "2"]),
+            Err(UrlError::ExtraParam("2".to_owned()))
+        );
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(join_path("", "/"), "/");
+        assert_eq!(join_path("/a", "/"), "/a");
+        assert_eq!(join_path("/a/", "b/{c}"), "/a/b/{c}");
+        assert_eq!(join_path("/a", "/b/"), "/a/b/");
+        assert_eq!(join_path("/a/", ""), "/a");
+    }
+}
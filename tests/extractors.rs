@@ -0,0 +1,480 @@
+#![feature(futures_api, async_await, await_macro)]
+
+#[macro_use]
+extern crate serde_derive;
+
+use futures::{executor::block_on, prelude::*, stream};
+use http_service::{Body, HttpService, Request, Response};
+use tide::{
+    body::{
+        BodyStream, ContentTypeCheck, FileUploads, Form, Json, Multipart, MultipartError,
+        MultipartLimits, MultipartStream, SizeLimit, UploadedFile,
+    },
+    head::{Params, Query},
+    Rejection, RenderRejection, Server,
+};
+
+struct TestBackend<T: HttpService> {
+    service: T,
+    connection: T::Connection,
+}
+
+impl<T: HttpService> TestBackend<T> {
+    fn wrap(service: T) -> Result<Self, <T::ConnectionFuture as TryFuture>::Error> {
+        let connection = block_on(service.connect().into_future())?;
+        Ok(Self {
+            service,
+            connection,
+        })
+    }
+
+    fn simulate(&mut self, req: Request) -> Result<Response, <T::Fut as TryFuture>::Error> {
+        block_on(
+            self.service
+                .respond(&mut self.connection, req)
+                .into_future(),
+        )
+    }
+}
+
+fn get(server: &mut TestBackend<Server<()>>, path: &str) -> (u16, String) {
+    let req = http::Request::get(path).body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    let status = res.status().as_u16();
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    (status, String::from_utf8(body).unwrap())
+}
+
+fn post(server: &mut TestBackend<Server<()>>, path: &str, body: &str) -> (u16, String) {
+    let req = http::Request::post(path)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.as_bytes().to_vec()))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    let status = res.status().as_u16();
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    (status, String::from_utf8(body).unwrap())
+}
+
+#[derive(Deserialize, Debug)]
+struct Filter {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Search {
+    page: u32,
+    sort: Option<String>,
+    #[serde(default)]
+    tag: Vec<String>,
+    filter: Option<Filter>,
+}
+
+#[test]
+fn query() {
+    let mut app = tide::App::new(());
+    app.at("/search").get(async move |Query(search): Query<Search>| {
+        format!(
+            "{} {:?} {:?} {:?}",
+            search.page,
+            search.sort,
+            search.tag,
+            search.filter.map(|filter| filter.name)
+        )
+    });
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/search?page=2&sort=name", r#"2 Some("name") [] None"#),
+        ("/search?page=1&tag=a&tag=b", r#"1 None ["a", "b"] None"#),
+        ("/search?page=1&tag[0]=a", r#"1 None ["a"] None"#),
+        ("/search?page=1&tag=a", r#"1 None ["a"] None"#),
+        ("/search?page=1&tag[]=a", r#"1 None ["a"] None"#),
+        ("/search?page=1&tag[]=a&tag[]=b", r#"1 None ["a", "b"] None"#),
+        ("/search?page=1&sort=first+name%21", r#"1 Some("first name!") [] None"#),
+        ("/search?page=3&filter[name]=tide", r#"3 None [] Some("tide")"#),
+    ] {
+        assert_eq!(get(&mut server, path), (200, expected.to_string()));
+    }
+
+    let (status, body) = get(&mut server, "/search?page=x");
+    assert_eq!(status, 400);
+    assert!(body.starts_with("Invalid query string"), "{}", body);
+    assert_eq!(get(&mut server, "/search").0, 400);
+}
+
+#[derive(Deserialize)]
+struct OrderPath {
+    customer: String,
+    id: u64,
+}
+
+#[test]
+fn params() {
+    let mut app = tide::App::new(());
+    app.at("/customers/{customer}/orders/{id}")
+        .get(async move |Params(path): Params<OrderPath>| {
+            format!("{} {}", path.customer, path.id)
+        });
+    app.at("/orders/{}/items/{}")
+        .get(async move |Params((order, item)): Params<(u64, String)>| {
+            format!("{} {}", order, item)
+        });
+    app.at("/users/{}")
+        .get(async move |Params(id): Params<u32>| id.to_string());
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/customers/ann%20lee/orders/7", "ann lee 7"),
+        ("/orders/3/items/lamp", "3 lamp"),
+        ("/users/12", "12"),
+    ] {
+        assert_eq!(get(&mut server, path), (200, expected.to_string()));
+    }
+
+    let (status, body) = get(&mut server, "/customers/ann/orders/latest");
+    assert_eq!(status, 400);
+    assert!(body.contains("segment `id`"), "{}", body);
+
+    let (status, body) = get(&mut server, "/orders/first/items/lamp");
+    assert_eq!(status, 400);
+    assert!(body.contains("segment `0`"), "{}", body);
+}
+
+#[test]
+fn rejections() {
+    let mut app = tide::App::new(());
+    app.at("/text")
+        .post(async move |Json(page): Json<u32>| page.to_string());
+    app.at("/api").nest(|router| {
+        router.config(RenderRejection::json());
+        router
+            .at("/pages")
+            .post(async move |Json(page): Json<u32>| page.to_string());
+    });
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    assert_eq!(post(&mut server, "/api/pages", "3"), (200, "3".to_string()));
+
+    let (status, body) = post(&mut server, "/text", "three");
+    assert_eq!(status, 400);
+    assert!(body.starts_with("Invalid JSON body: "), "{}", body);
+
+    let (status, body) = post(&mut server, "/api/pages", "three");
+    assert_eq!(status, 400);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["error"], "malformed");
+    assert_eq!(body["extractor"], "body::Json");
+    assert!(body["message"].as_str().unwrap().starts_with("Invalid JSON body: "));
+}
+
+#[test]
+fn optional_and_fallible() {
+    let mut app = tide::App::new(());
+    app.at("/pages").post(async move |page: Option<Json<u32>>| {
+        format!("{:?}", page.map(|Json(page)| page))
+    });
+    app.at("/search")
+        .get(async move |search: Result<Query<Search>, Rejection>| match search {
+            Ok(Query(search)) => format!("page {}", search.page),
+            Err(rejection) => format!("{:?} from {}", rejection.kind(), rejection.extractor()),
+        });
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    assert_eq!(post(&mut server, "/pages", "3"), (200, "Some(3)".to_string()));
+    assert_eq!(post(&mut server, "/pages", ""), (200, "None".to_string()));
+    assert_eq!(post(&mut server, "/pages", "three").0, 400);
+
+    let untyped = |body: &str| {
+        http::Request::post("/pages")
+            .body(Body::from(body.as_bytes().to_vec()))
+            .unwrap()
+    };
+    let res = server.simulate(untyped("")).unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(block_on(res.into_body().into_vec()).unwrap(), b"None");
+    let res = server.simulate(untyped("3")).unwrap();
+    assert_eq!(res.status(), 415);
+
+    assert_eq!(get(&mut server, "/search?page=2"), (200, "page 2".to_string()));
+    assert_eq!(
+        get(&mut server, "/search?page=x"),
+        (200, "Malformed from head::Query".to_string())
+    );
+}
+
+#[test]
+fn body_size_limit() {
+    let mut app = tide::App::new(());
+    app.config(SizeLimit(4));
+    app.at("/small")
+        .post(async move |Json(page): Json<u32>| page.to_string());
+    app.at("/large")
+        .post(async move |Json(page): Json<u32>| page.to_string())
+        .config(SizeLimit(1024));
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    assert_eq!(post(&mut server, "/small", "1234"), (200, "1234".to_string()));
+    let (status, body) = post(&mut server, "/small", "12345");
+    assert_eq!(status, 413);
+    assert_eq!(body, "Request body exceeds the limit of 4 bytes");
+    assert_eq!(post(&mut server, "/large", "12345"), (200, "12345".to_string()));
+
+    let req = http::Request::post("/small")
+        .header("Content-Length", "4096")
+        .body(Body::from(b"1".to_vec()))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 413);
+}
+
+#[test]
+fn content_type_check() {
+    let mut app = tide::App::new(());
+    app.at("/json")
+        .post(async move |Json(page): Json<u32>| page.to_string());
+    app.at("/form")
+        .post(async move |Form(filter): Form<Filter>| filter.name);
+    app.at("/legacy")
+        .post(async move |Json(page): Json<u32>| page.to_string())
+        .config(ContentTypeCheck::Ignore);
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let mut send = |path: &str, content_type: Option<&str>, body: &str| {
+        let mut req = http::Request::post(path);
+        if let Some(content_type) = content_type {
+            req.header("Content-Type", content_type);
+        }
+        let req = req.body(Body::from(body.as_bytes().to_vec())).unwrap();
+        server.simulate(req).unwrap().status().as_u16()
+    };
+
+    assert_eq!(send("/json", Some("application/json; charset=utf-8"), "1"), 200);
+    assert_eq!(send("/json", Some("application/vnd.api+json"), "1"), 200);
+    assert_eq!(send("/json", Some("text/plain"), "1"), 415);
+    assert_eq!(send("/json", None, "1"), 415);
+    assert_eq!(send("/form", Some("application/x-www-form-urlencoded"), "name=a"), 200);
+    assert_eq!(send("/form", Some("application/json"), "name=a"), 415);
+    assert_eq!(send("/legacy", Some("text/plain"), "1"), 200);
+    assert_eq!(send("/legacy", None, "1"), 200);
+}
+
+#[test]
+fn body_stream() {
+    let mut app = tide::App::new(());
+    app.config(SizeLimit(8));
+    app.at("/chunks").post(async move |mut body: BodyStream| {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = await!(body.next()) {
+            match chunk {
+                Ok(chunk) => chunks.push(String::from_utf8(chunk.to_vec()).unwrap()),
+                Err(_) => chunks.push("error".to_string()),
+            }
+        }
+        chunks.join(",")
+    });
+    app.at("/read").post(async move |body: BodyStream| {
+        let mut buf = Vec::new();
+        await!(body.into_reader().read_to_end(&mut buf)).unwrap();
+        String::from_utf8(buf).unwrap()
+    });
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let mut send = |path: &str, chunks: &[&'static str]| {
+        let chunks: Vec<_> = chunks
+            .iter()
+            .map(|chunk| Ok::<_, std::io::Error>(chunk.as_bytes().into()))
+            .collect();
+        let req = http::Request::post(path)
+            .body(Body::from_stream(stream::iter(chunks)))
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let status = res.status().as_u16();
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        (status, String::from_utf8(body).unwrap())
+    };
+
+    assert_eq!(send("/chunks", &["abc", "de"]), (200, "abc,de".to_string()));
+    assert_eq!(send("/chunks", &["abcde", "fghij"]), (200, "abcde,error".to_string()));
+    assert_eq!(send("/read", &["abc", "de", "f"]), (200, "abcdef".to_string()));
+}
+
+const MULTIPART_BODY: &str = "preamble\r\n\
+    --XyZ\r\n\
+    Content-Disposition: form-data; name=\"title\"\r\n\
+    \r\n\
+    Holiday\r\n\
+    --XyZ\r\n\
+    Content-Disposition: form-data; name=\"photo\"; filename=\"beach.txt\"\r\n\
+    Content-Type: text/plain\r\n\
+    \r\n\
+    sand\r\n--and sea\r\n\
+    --XyZ--\r\n";
+
+fn post_multipart(
+    server: &mut TestBackend<Server<()>>,
+    path: &str,
+    chunk: usize,
+) -> (u16, String) {
+    post_multipart_body(server, path, MULTIPART_BODY, chunk)
+}
+
+fn post_multipart_body(
+    server: &mut TestBackend<Server<()>>,
+    path: &str,
+    body: &str,
+    chunk: usize,
+) -> (u16, String) {
+    let chunks: Vec<_> = body
+        .as_bytes()
+        .chunks(chunk)
+        .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec().into()))
+        .collect();
+    let req = http::Request::post(path)
+        .header("Content-Type", "multipart/form-data; boundary=XyZ")
+        .body(Body::from_stream(stream::iter(chunks)))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    let status = res.status().as_u16();
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    (status, String::from_utf8(body).unwrap())
+}
+
+async fn upload(mut form: MultipartStream) -> Result<String, MultipartError> {
+    let mut fields = Vec::new();
+    while let Some(field) = await!(form.next_field())? {
+        let name = field.name().to_owned();
+        let meta = format!("{:?} {:?}", field.filename(), field.content_type());
+        fields.push(format!("{} {} {:?}", name, meta, await!(field.text())?));
+    }
+    Ok(fields.join("\n"))
+}
+
+async fn field_names(mut form: MultipartStream) -> Result<String, MultipartError> {
+    let mut names = Vec::new();
+    while let Some(field) = await!(form.next_field())? {
+        names.push(field.name().to_owned());
+    }
+    Ok(names.join(" "))
+}
+
+#[test]
+fn multipart_stream() {
+    let mut app = tide::App::new(());
+    app.at("/upload").post(upload);
+    app.at("/small").post(upload).config(MultipartLimits {
+        field: 8,
+        total: 1024,
+    });
+    app.at("/names").post(field_names).config(MultipartLimits {
+        field: 8,
+        total: 1024,
+    });
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let expected = "title None None \"Holiday\"\n\
+                    photo Some(\"beach.txt\") Some(\"text/plain\") \"sand\\r\\n--and sea\"";
+    for chunk in &[1, 3, 7, 1024] {
+        assert_eq!(
+            post_multipart(&mut server, "/upload", *chunk),
+            (200, expected.to_string())
+        );
+    }
+
+    let (status, body) = post_multipart(&mut server, "/small", 5);
+    assert_eq!(status, 413);
+    assert_eq!(body, "Multipart field `photo` exceeds the size limit");
+    assert_eq!(
+        post_multipart(&mut server, "/names", 5),
+        (200, "title photo".to_string())
+    );
+
+    let padded = MULTIPART_BODY
+        .replace("--XyZ\r\n", "--XyZ \t \r\n")
+        .replace("--XyZ--", "--XyZ--\t");
+    for chunk in &[1, 1024] {
+        assert_eq!(
+            post_multipart_body(&mut server, "/upload", &padded, *chunk),
+            (200, expected.to_string())
+        );
+    }
+
+    let (status, _) = post(&mut server, "/upload", "{}");
+    assert_eq!(status, 415);
+}
+
+#[derive(Deserialize)]
+struct Album {
+    title: String,
+    photo: UploadedFile,
+}
+
+#[derive(Deserialize)]
+struct Caption {
+    title: UploadedFile,
+}
+
+#[derive(Deserialize)]
+struct Misfiled {
+    photo: String,
+}
+
+#[test]
+fn multipart_form() {
+    let dir = std::env::temp_dir().join(format!("tide-test-uploads-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let uploads = FileUploads {
+        dir: dir.clone(),
+        max_size: 1024,
+    };
+
+    let mut app = tide::App::new(());
+    app.at("/album")
+        .post(async move |Multipart(album): Multipart<Album>| {
+            let content = std::fs::read_to_string(album.photo.path()).unwrap();
+            format!(
+                "{} {:?} {:?} {} {:?}",
+                album.title,
+                album.photo.filename(),
+                album.photo.content_type(),
+                album.photo.size(),
+                content,
+            )
+        })
+        .config(uploads.clone());
+    app.at("/small").post(async move |_: Multipart<Album>| "").config(FileUploads {
+        max_size: 8,
+        ..uploads.clone()
+    });
+    app.at("/caption")
+        .post(async move |_: Multipart<Caption>| "")
+        .config(uploads.clone());
+    app.at("/misfiled")
+        .post(async move |Multipart(form): Multipart<Misfiled>| form.photo)
+        .config(uploads);
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let expected = "Holiday Some(\"beach.txt\") Some(\"text/plain\") 15 \"sand\\r\\n--and sea\"";
+    for chunk in &[1, 7, 1024] {
+        assert_eq!(
+            post_multipart(&mut server, "/album", *chunk),
+            (200, expected.to_string())
+        );
+    }
+
+    let (status, _) = post_multipart(&mut server, "/small", 1024);
+    assert_eq!(status, 413);
+
+    // Text never turns into a file, and files never turn into text.
+    let (status, body) = post_multipart(&mut server, "/caption", 1024);
+    assert_eq!(status, 400);
+    assert!(body.contains("expected an uploaded file"), "{}", body);
+
+    let (status, body) = post_multipart(&mut server, "/misfiled", 1024);
+    assert_eq!(status, 400);
+    assert!(body.contains("expected a string, found an uploaded file"), "{}", body);
+
+    // Spooled files are removed once the handler is done with them, or on failure.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    std::fs::remove_dir(&dir).unwrap();
+}
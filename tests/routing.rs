@@ -0,0 +1,372 @@
+#![feature(futures_api, async_await)]
+
+use futures::{executor::block_on, prelude::*};
+use http_service::{Body, HttpService, Request, Response};
+use tide::{AllowedMethods, ExtractConfiguration, IntoResponse, Server};
+
+struct TestBackend<T: HttpService> {
+    service: T,
+    connection: T::Connection,
+}
+
+impl<T: HttpService> TestBackend<T> {
+    fn wrap(service: T) -> Result<Self, <T::ConnectionFuture as TryFuture>::Error> {
+        let connection = block_on(service.connect().into_future())?;
+        Ok(Self {
+            service,
+            connection,
+        })
+    }
+
+    fn simulate(&mut self, req: Request) -> Result<Response, <T::Fut as TryFuture>::Error> {
+        block_on(
+            self.service
+                .respond(&mut self.connection, req)
+                .into_future(),
+        )
+    }
+}
+
+fn make_server() -> TestBackend<Server<()>> {
+    let mut app = tide::App::new(());
+    app.at("/items").get(async || "items");
+    app.at("/items").post(async || "created");
+    TestBackend::wrap(app.into_http_service()).unwrap()
+}
+
+#[test]
+fn method_not_allowed() {
+    let mut server = make_server();
+
+    let req = http::Request::delete("/items")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 405);
+    assert_eq!(res.headers()["Allow"], "GET, HEAD, OPTIONS, POST");
+
+    let req = http::Request::delete("/other")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 404);
+    assert!(res.headers().get("Allow").is_none());
+}
+
+#[test]
+fn custom_method_not_allowed() {
+    let mut app = tide::App::new(());
+    app.at("/items").get(async || "items");
+    app.method_not_allowed_handler(async move |allowed: AllowedMethods| {
+        format!("{} methods allowed", allowed.0.len())
+            .with_status(http::status::StatusCode::METHOD_NOT_ALLOWED)
+    });
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let req = http::Request::put("/items").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 405);
+    assert_eq!(res.headers()["Allow"], "GET, HEAD, OPTIONS");
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"3 methods allowed");
+}
+
+#[test]
+fn automatic_options() {
+    let mut server = make_server();
+
+    let req = http::Request::options("/items")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["Allow"], "GET, HEAD, OPTIONS, POST");
+}
+
+#[test]
+fn url_for_extractor() {
+    let mut app = tide::App::new(());
+    app.at("/messages").post(async move |urls: tide::Urls| {
+        urls.url_for("message", &["42"]).unwrap()
+    });
+    app.at("/messages/{id}").name("message").get(async || "message");
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/messages")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"/messages/42");
+}
+
+#[test]
+fn trailing_slash_redirect() {
+    let mut app = tide::App::new(());
+    app.config(tide::TrailingSlash::Redirect(
+        http::StatusCode::MOVED_PERMANENTLY,
+    ));
+    app.at("/articles").get(async || "articles");
+    app.at("/docs/").get(async || "docs");
+    app.at("/{slug}").get(async || "slug");
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/articles/?page=2")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers()["Location"], "/articles?page=2");
+
+    let req = http::Request::get("/docs").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers()["Location"], "/docs/");
+
+    // Repeated slashes must not turn into a protocol-relative URL.
+    for (path, location) in &[("//evil.com/", "/evil.com"), ("///articles//", "/articles")] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 301);
+        assert_eq!(res.headers()["Location"], *location);
+    }
+
+    let req = http::Request::get("/articles")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+}
+
+#[test]
+fn host_routing() {
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "www".to_string());
+    app.host("api.example.com")
+        .at("/")
+        .get(async || "api".to_string());
+    app.host("*.example.com").at("/").get(
+        async move |tide::head::Subdomain(tenant): tide::head::Subdomain| {
+            format!("tenant {}", tenant)
+        },
+    );
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (host, expected) in &[
+        ("api.example.com", "api"),
+        ("acme.example.com:8080", "tenant acme"),
+        ("example.com", "www"),
+        ("other.org", "www"),
+    ] {
+        let req = http::Request::get("/")
+            .header("Host", *host)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+}
+
+#[test]
+fn mount_service() {
+    let mut legacy = tide::App::new(());
+    legacy.at("/users/{}").get(
+        async move |head: tide::head::Head, original: tide::OriginalUri| {
+            format!("{} from {}", head.uri(), original.0)
+        },
+    );
+
+    let mut app = tide::App::new(());
+    app.at("/legacy").mount(legacy.into_http_service());
+    app.at("/").get(async || "index");
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/legacy/users/3?full=1")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"/users/3?full=1 from /legacy/users/3?full=1");
+
+    let req = http::Request::post("/legacy").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[test]
+fn multiple_and_extension_methods() {
+    let propfind = http::Method::from_bytes(b"PROPFIND").unwrap();
+    let mkcol = http::Method::from_bytes(b"MKCOL").unwrap();
+
+    let mut app = tide::App::new(());
+    app.at("/files/*")
+        .methods(&[http::Method::PUT, mkcol.clone()], async move |head: tide::head::Head| {
+            format!("write {}", head.method())
+        });
+    app.at("/files/*").method(propfind.clone(), async || "properties");
+    app.at("/echo")
+        .any(async move |head: tide::head::Head| head.method().to_string());
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (method, path, expected) in &[
+        (http::Method::PUT, "/files/a", "write PUT"),
+        (mkcol.clone(), "/files/dir", "write MKCOL"),
+        (propfind.clone(), "/files/a", "properties"),
+        (http::Method::DELETE, "/echo", "DELETE"),
+        (propfind.clone(), "/echo", "PROPFIND"),
+    ] {
+        let req = http::Request::builder()
+            .method(method.clone())
+            .uri(*path)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+
+    let req = http::Request::get("/files/a").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 405);
+    assert_eq!(res.headers()["Allow"], "MKCOL, OPTIONS, PROPFIND, PUT");
+}
+
+#[test]
+fn guards() {
+    use tide::guard;
+
+    let mut app = tide::App::new(());
+    app.at("/items")
+        .get(async || "json")
+        .guard(guard::accept("application/json"));
+    app.at("/items")
+        .get(async || "csv")
+        .guard(guard::accept("text/csv"));
+    app.at("/items")
+        .post(async || "created")
+        .guard(guard::content_type("application/json"));
+    app.at("/beta")
+        .get(async || "beta")
+        .guard(guard::query("beta"));
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (accept, status, expected) in &[
+        ("application/json", 200, "json"),
+        ("text/csv", 200, "csv"),
+        ("text/*", 200, "csv"),
+        ("text/html", 406, ""),
+    ] {
+        let req = http::Request::get("/items")
+            .header("Accept", *accept)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), *status);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+
+    let req = http::Request::post("/items")
+        .header("Content-Type", "text/csv")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 415);
+
+    let req = http::Request::get("/beta").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 404);
+
+    let req = http::Request::get("/beta?beta").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+}
+
+#[test]
+fn runtime_updates() {
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "index");
+    let service = app.into_http_service();
+    let handle = service.handle();
+    let mut server = TestBackend::wrap(service).unwrap();
+
+    let get = |server: &mut TestBackend<Server<()>>, host: &str, path: &str| {
+        let req = http::Request::get(path)
+            .header("Host", host)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let status = res.status();
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        (status, String::from_utf8(body).unwrap())
+    };
+
+    assert_eq!(get(&mut server, "example.com", "/plugin").0, 404);
+
+    handle.update(|router| {
+        router.at("/plugin").get(async || "plugin");
+    });
+    handle.update_host("acme.example.com", |router| {
+        router.at("/").get(async || "acme");
+    });
+    assert_eq!(get(&mut server, "example.com", "/plugin").1, "plugin");
+    assert_eq!(get(&mut server, "example.com", "/").1, "index");
+    assert_eq!(get(&mut server, "acme.example.com", "/").1, "acme");
+
+    let mut router = handle.new_router();
+    router.at("/").get(async || "replaced");
+    handle.replace(router);
+    assert_eq!(get(&mut server, "example.com", "/").1, "replaced");
+    assert_eq!(get(&mut server, "example.com", "/plugin").0, 404);
+}
+
+async fn greeting(ExtractConfiguration(greeting): ExtractConfiguration<&'static str>) -> String {
+    greeting.unwrap_or("none").to_string()
+}
+
+#[test]
+fn runtime_config_updates() {
+    let mut app = tide::App::new(());
+    app.config("hello");
+    app.at("/greeting").get(greeting);
+    app.at("/fixed").get(greeting).config("fixed");
+    app.host("acme.example.com").at("/greeting").get(greeting);
+    let service = app.into_http_service();
+    let handle = service.handle();
+    let mut server = TestBackend::wrap(service).unwrap();
+
+    let mut get = |host: &str, path: &str| {
+        let req = http::Request::get(path)
+            .header("Host", host)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        String::from_utf8(body).unwrap()
+    };
+
+    assert_eq!(get("example.com", "/greeting"), "hello");
+    assert_eq!(get("acme.example.com", "/greeting"), "hello");
+
+    handle.update(|router| {
+        router.config("hi");
+        router.at("/new").get(greeting);
+    });
+    assert_eq!(get("example.com", "/greeting"), "hi");
+    assert_eq!(get("example.com", "/new"), "hi");
+    assert_eq!(get("example.com", "/fixed"), "fixed");
+    assert_eq!(get("acme.example.com", "/greeting"), "hi");
+
+    handle.update_host("acme.example.com", |router| {
+        router.config("acme");
+    });
+    handle.update(|router| {
+        router.config("hey");
+    });
+    assert_eq!(get("example.com", "/greeting"), "hey");
+    assert_eq!(get("acme.example.com", "/greeting"), "acme");
+}
@@ -3,7 +3,7 @@
 use futures::{executor::block_on, prelude::*};
 use http_service::{Body, HttpService, Request, Response};
 use tide::{
-    head::{Named, NamedSegment},
+    head::{Named, NamedSegment, Path, RawPath},
     Server,
 };
 
@@ -44,6 +44,20 @@ impl std::str::FromStr for Number {
     }
 }
 
+struct CatchAll(String);
+
+impl NamedSegment for CatchAll {
+    const NAME: &'static str = "path";
+}
+
+impl std::str::FromStr for CatchAll {
+    type Err = std::string::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(CatchAll(s.to_owned()))
+    }
+}
+
 async fn add_one(Named(Number(num)): Named<Number>) -> String {
     (num + 1).to_string()
 }
@@ -94,3 +108,86 @@ fn not_found_error() {
     let res = server.simulate(req).unwrap();
     assert_eq!(res.status(), 404);
 }
+
+#[test]
+fn percent_decoding() {
+    let mut app = tide::App::new(());
+    app.at("/files/{}").get(async move |Path(name): Path<String>| name);
+    app.at("/raw/{}").get(async move |RawPath(name): RawPath<String>| name);
+    app.at("/add_one/{num}").get(add_one);
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/files/my%20doc", "my doc"),
+        ("/raw/my%20doc", "my%20doc"),
+        ("/raw/a%2Fb", "a%2Fb"),
+        ("/add_one/%34", "5"),
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+
+    for path in &["/files/%FF", "/files/a%2Fb"] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
+
+#[test]
+fn catch_all_decoding() {
+    let mut app = tide::App::new(());
+    app.at("/static/{path}*")
+        .get(async move |Path(path): Path<String>| path);
+    app.at("/named/{path}*")
+        .get(async move |Named(path): Named<CatchAll>| path.0);
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/static/css/main%20file.css", "css/main file.css"),
+        ("/static/a/../etc", "a/../etc"),
+        ("/static/%C3%A9t%C3%A9/x", "été/x"),
+        ("/named/a%20b/c", "a b/c"),
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+
+    for path in &[
+        "/static/a%2F..%2Fetc",
+        "/static/a/..%2fetc",
+        "/static/x/%FF",
+        "/named/a%2Fb",
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 400);
+    }
+}
+
+#[test]
+fn differently_named_wildcards() {
+    let mut app = tide::App::new(());
+    app.at("/add_one/{num}").get(add_one);
+    app.at("/add_one/{n}")
+        .post(async move |Path(n): Path<i32>| format!("post {}", n));
+    let mut server = TestBackend::wrap(app.into_http_service()).unwrap();
+
+    for (method, expected) in &[(http::Method::GET, "4"), (http::Method::POST, "post 3")] {
+        let req = http::Request::builder()
+            .method(method)
+            .uri("/add_one/3")
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+}